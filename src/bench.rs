@@ -0,0 +1,288 @@
+use crate::{
+    core::{self, RunOptions, RunOutcome, RunStatus},
+    diagnostic,
+    file_type::FileType,
+    utils::project_cache_dir,
+};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{path::Path, time::Duration};
+
+/// Timing statistics of a benchmark, in seconds
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub runs: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    pub fn new(samples: &[f64]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let runs = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / runs.max(1) as f64;
+
+        let median = match runs {
+            0 => 0.0,
+            n if n % 2 == 0 => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+            n => sorted[n / 2],
+        };
+
+        // sample standard deviation
+        let stddev = match runs {
+            0 | 1 => 0.0,
+            n => (sorted.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt(),
+        };
+
+        Self {
+            runs,
+            mean,
+            median,
+            stddev,
+            min: sorted.first().copied().unwrap_or_default(),
+            max: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+
+    /// whether the means of `self` and `other` differ at 95% confidence (Welch's t-test)
+    pub fn differs_from(&self, other: &Stats) -> bool {
+        if self.runs < 2 || other.runs < 2 {
+            return false;
+        }
+
+        let a = self.stddev.powi(2) / self.runs as f64;
+        let b = other.stddev.powi(2) / other.runs as f64;
+
+        if a + b == 0.0 {
+            return self.mean != other.mean;
+        }
+
+        let (t, df) = self.welch(other);
+        t > critical_t(df)
+    }
+
+    /// Welch's t statistic of the difference of the means and its degrees of freedom,
+    /// for samples of at least 2 runs that don't all take the same time
+    fn welch(&self, other: &Stats) -> (f64, f64) {
+        let a = self.stddev.powi(2) / self.runs as f64;
+        let b = other.stddev.powi(2) / other.runs as f64;
+
+        let t = (self.mean - other.mean).abs() / (a + b).sqrt();
+        let df = (a + b).powi(2)
+            / (a.powi(2) / (self.runs - 1) as f64 + b.powi(2) / (other.runs - 1) as f64);
+
+        (t, df)
+    }
+}
+
+/// Samples of the benchmark later ones are compared to, kept in the file's cache directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Baseline {
+    samples: Vec<f64>,
+}
+
+impl Baseline {
+    fn path(path: &Path) -> anyhow::Result<std::path::PathBuf> {
+        Ok(project_cache_dir("bench", path)?.join("baseline.json"))
+    }
+
+    fn load(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path(path).ok()?).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(Self::path(path)?, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// builds `path` once, runs it `warmups` times unmeasured, then `runs` times,
+/// and prints the statistics of the runs against the saved baseline
+///
+/// the first benchmark of a file becomes its baseline, `save_baseline` replaces it with the current one
+pub async fn run(
+    file_type: &FileType,
+    path: &Path,
+    options: &RunOptions,
+    runs: usize,
+    warmups: usize,
+    save_baseline: bool,
+) -> anyhow::Result<()> {
+    let options = RunOptions {
+        capture: true,
+        ..options.clone()
+    };
+
+    let prepared = core::prepare(file_type, path, &options).await?;
+    if prepared.build_failed() {
+        let outcome = prepared.execute().await?;
+        diagnostic::render(&outcome.diagnostics);
+        return Ok(());
+    }
+
+    let mut s = spinners::Spinner::new(
+        spinners::Spinners::Dots,
+        format!("Benchmarking {runs} runs after {warmups} warmups..."),
+    );
+
+    let mut samples = vec![];
+    let mut last = RunOutcome::default();
+
+    for i in 0..warmups + runs {
+        last = prepared.execute().await?;
+
+        // a failing program is not worth measuring
+        if last.status != RunStatus::Success {
+            s.stop_with_symbol("✖");
+            print!("{}", last.output.as_deref().unwrap_or_default());
+            eprintln!("💥 Exited with: {}", last.status.to_string().red());
+            return Ok(());
+        }
+
+        if i >= warmups {
+            samples.push(last.run_duration.as_secs_f64());
+        }
+    }
+
+    s.stop_with_symbol("✔");
+    eprintln!();
+    print!("{}", last.output.as_deref().unwrap_or_default());
+
+    let stats = Stats::new(&samples);
+    let baseline = Baseline::load(path);
+
+    eprintln!();
+    diagnostic::render(&last.diagnostics);
+    render(&stats, baseline.as_ref().map(|b| Stats::new(&b.samples)));
+
+    if save_baseline || baseline.is_none() {
+        Baseline { samples }.save(path)?;
+        eprintln!("💾 Saved as the baseline");
+    }
+
+    Ok(())
+}
+
+fn render(stats: &Stats, baseline: Option<Stats>) {
+    eprintln!("📊 {} runs", stats.runs);
+    eprintln!(
+        "   mean    {} ± {}",
+        format_secs(stats.mean).bold(),
+        format_secs(stats.stddev)
+    );
+    eprintln!("   median  {}", format_secs(stats.median));
+    eprintln!(
+        "   range   {} … {}",
+        format_secs(stats.min),
+        format_secs(stats.max)
+    );
+
+    let Some(baseline) = baseline else {
+        return;
+    };
+
+    let change = (stats.mean - baseline.mean) / baseline.mean * 100.0;
+    let comparison = format!(
+        "{} → {} ({change:+.1}%)",
+        format_secs(baseline.mean),
+        format_secs(stats.mean)
+    );
+
+    let verdict = match verdict(stats, &baseline) {
+        "faster" => "faster".green(),
+        "slower" => "slower".red(),
+        verdict => verdict.dimmed(),
+    };
+
+    eprintln!("⚖️ Baseline: {comparison}, {verdict}");
+}
+
+/// how `stats` compares to `baseline`
+fn verdict(stats: &Stats, baseline: &Stats) -> &'static str {
+    if !stats.differs_from(baseline) {
+        "no significant change"
+    } else if stats.mean < baseline.mean {
+        "faster"
+    } else {
+        "slower"
+    }
+}
+
+fn format_secs(secs: f64) -> String {
+    format!("{:.2?}", Duration::from_secs_f64(secs))
+}
+
+/// two-sided 95% critical value of Student's t distribution,
+/// rounding the degrees of freedom down to stay conservative
+fn critical_t(df: f64) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+
+    match df.floor() as usize {
+        0 => f64::INFINITY,
+        df @ 1..=30 => TABLE[df - 1],
+        31..=40 => 2.042,
+        41..=60 => 2.021,
+        61..=120 => 2.000,
+        _ => 1.980,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn median_of_an_even_sample() {
+        let stats = Stats::new(&[4.0, 1.0, 3.0, 2.0]);
+
+        assert_eq!(stats.median, 2.5);
+        assert_eq!((stats.min, stats.max), (1.0, 4.0));
+    }
+
+    #[test]
+    fn sample_stddev() {
+        let stats = Stats::new(&[1.0, 2.0, 3.0, 4.0]);
+
+        assert_eq!(stats.mean, 2.5);
+        // sqrt(5 / 3), divided by n - 1
+        assert!(close(stats.stddev, 1.291));
+    }
+
+    #[test]
+    fn welch_t_and_df() {
+        let a = Stats::new(&[1.0, 2.0, 3.0, 4.0]);
+        let b = Stats::new(&[2.0, 3.0, 4.0, 5.0]);
+
+        let (t, df) = a.welch(&b);
+
+        assert!(close(t, 1.095));
+        assert!(close(df, 6.0));
+        assert_eq!(critical_t(df), 2.447);
+    }
+
+    #[test]
+    fn verdicts() {
+        let fast = Stats::new(&[1.0, 1.1, 0.9, 1.0]);
+        let slow = Stats::new(&[2.0, 2.1, 1.9, 2.0]);
+        let overlapping = Stats::new(&[1.0, 2.0, 3.0, 4.0]);
+        let shifted = Stats::new(&[2.0, 3.0, 4.0, 5.0]);
+
+        assert_eq!(verdict(&fast, &slow), "faster");
+        assert_eq!(verdict(&slow, &fast), "slower");
+        assert_eq!(verdict(&overlapping, &shifted), "no significant change");
+    }
+}
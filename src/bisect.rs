@@ -0,0 +1,195 @@
+use crate::cli::BisectArgs;
+use anyhow::{anyhow, bail};
+use colored::*;
+use runner::{
+    core::{self, RunOptions, RunOutcome, RunStatus},
+    diagnostic::Diagnostic,
+    diff,
+    file_type::FileType,
+    history::{History, Run},
+};
+use std::path::Path;
+
+/// replays the runs recorded between `--good` and `--bad` in a sandbox, halving the range each time,
+/// and prints the first bad one with the source diff that made it bad
+pub async fn run(args: &BisectArgs) -> anyhow::Result<()> {
+    if args.good >= args.bad {
+        bail!("--good must be a run before --bad");
+    }
+
+    let history = History::open()?;
+    let (good, bad) = (history.get(args.good)?, history.get(args.bad)?);
+
+    if good.path != bad.path {
+        bail!(
+            "#{} ran {} but #{} ran {}",
+            good.number,
+            good.path.display(),
+            bad.number,
+            bad.path.display()
+        );
+    }
+
+    // saves that didn't change the source behave the same, only the first of them is replayed
+    let mut runs: Vec<Run> = vec![];
+    for run in history.runs()? {
+        let in_range = (good.number..=bad.number).contains(&run.number) && run.path == good.path;
+        let changed = runs.last().is_none_or(|last| last.source != run.source);
+
+        if in_range && changed {
+            runs.push(run);
+        }
+    }
+
+    if runs.len() < 2 {
+        bail!(
+            "#{} and #{} ran the same source, there is no edit to find",
+            good.number,
+            bad.number
+        );
+    }
+
+    let sandbox = std::env::temp_dir().join(format!("runner-bisect-{}", std::process::id()));
+    std::fs::create_dir_all(&sandbox)?;
+
+    let result = bisect(&runs, args, &sandbox).await;
+    log::debug!("removing the sandbox {}", sandbox.display());
+    // a leftover sandbox isn't worth losing the result over
+    if let Err(e) = std::fs::remove_dir_all(&sandbox) {
+        eprintln!("⚠️ Failed to remove the sandbox {}: {e}", sandbox.display());
+    }
+
+    let (last_good, first_bad) = result?;
+
+    eprintln!();
+    eprintln!(
+        "{}",
+        format!("🎯 #{} is the first bad run", first_bad.number).bold()
+    );
+    eprintln!();
+    eprintln!(
+        "{}",
+        format!("── source #{} → #{} ──", last_good.number, first_bad.number).bold()
+    );
+
+    if !diff::render(&last_good.source, &first_bad.source, 3) {
+        eprintln!(
+            "{}",
+            "no changes, the difference comes from outside the file".dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+/// the last good and the first bad of `runs`, which start with a good run and end with a bad one
+async fn bisect<'a>(
+    runs: &'a [Run],
+    args: &BisectArgs,
+    sandbox: &Path,
+) -> anyhow::Result<(&'a Run, &'a Run)> {
+    let (first, last) = (&runs[0], &runs[runs.len() - 1]);
+    let steps = (runs.len().saturating_sub(1) as f64).log2().ceil() as usize;
+    eprintln!(
+        "🔎 Bisecting {} runs with different sources, {steps} replays between #{} and #{}",
+        runs.len(),
+        first.number,
+        last.number
+    );
+
+    let mut judge = Judge {
+        args,
+        options: args.run_options(),
+        sandbox,
+        reference: None,
+    };
+
+    if !judge.is_good(first).await {
+        bail!(
+            "#{} is bad when replayed, pick an earlier --good",
+            first.number
+        );
+    }
+
+    if judge.is_good(last).await {
+        bail!("#{} is good when replayed, pick a later --bad", last.number);
+    }
+
+    let (mut good, mut bad) = (0, runs.len() - 1);
+    while bad - good > 1 {
+        let middle = (good + bad) / 2;
+        if judge.is_good(&runs[middle]).await {
+            good = middle;
+        } else {
+            bad = middle;
+        }
+    }
+
+    Ok((&runs[good], &runs[bad]))
+}
+
+/// Replays runs and tells whether they're good
+struct Judge<'a> {
+    args: &'a BisectArgs,
+    options: RunOptions,
+    sandbox: &'a Path,
+    /// output of the first run replayed, the good one, for `--same-output`
+    reference: Option<Option<String>>,
+}
+
+impl Judge<'_> {
+    async fn is_good(&mut self, run: &Run) -> bool {
+        let mut s = spinners::Spinner::new(
+            spinners::Spinners::Dots,
+            format!("Replaying #{}...", run.number),
+        );
+
+        let outcome = match replay(run, self.sandbox, &self.options).await {
+            Ok(outcome) => outcome,
+            // counted as bad like a failed build
+            Err(e) => RunOutcome {
+                status: RunStatus::BuildFailed,
+                diagnostics: vec![Diagnostic::error(&e.to_string())],
+                ..Default::default()
+            },
+        };
+
+        let reference = self.reference.get_or_insert_with(|| outcome.output.clone());
+
+        let good = match &self.args.output_contains {
+            Some(text) => outcome.output.as_deref().unwrap_or_default().contains(text),
+            None if self.args.same_output => outcome.output == *reference,
+            None => outcome.status == RunStatus::Success,
+        };
+
+        let (symbol, verdict) = if good {
+            ("✔", "good".green())
+        } else {
+            ("✖", "bad".red())
+        };
+
+        s.stop_and_persist(
+            symbol,
+            format!(
+                "#{} is {verdict}, exited with {}",
+                run.number, outcome.status
+            ),
+        );
+
+        good
+    }
+}
+
+/// runs the source of `run` from `sandbox`, under the same file name
+async fn replay(run: &Run, sandbox: &Path, options: &RunOptions) -> anyhow::Result<RunOutcome> {
+    let name = run
+        .path
+        .file_name()
+        .ok_or(anyhow!("#{} has no file name", run.number))?;
+
+    let path = sandbox.join(name);
+    std::fs::write(&path, &run.source)?;
+
+    let file_type = FileType::try_from(&path)?;
+    core::run(&file_type, &path, options).await
+}
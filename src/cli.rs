@@ -1,9 +1,32 @@
-use crate::file_type::FileType;
-use clap::Parser;
-use std::path::PathBuf;
+use crate::matrix::Matrix;
+use clap::{builder::TypedValueParser, Parser, Subcommand};
+use runner::{
+    core::RunOptions, daemon::Readiness, diff::OutputDiff, file_type::FileType, proxy::Port,
+};
+use std::{io::IsTerminal, path::PathBuf, time::Duration};
 
 #[derive(Debug, Clone, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub subcommand: Option<Commands>,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+
+    /// log what runner does, `-v` for detection, commands with their timings, docker and cleanup,
+    /// `-vv` also for every watcher event and docker stats sample
+    #[clap(short, long, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// append the log to this file instead of stderr, at the `-v` level unless `-vv` is given
+    #[clap(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+}
+
+/// How to watch and run a file, also taken by `runner run`
+#[derive(Debug, Clone, clap::Args)]
+pub struct RunArgs {
     /// path to the file to watch
     pub path: Option<PathBuf>,
 
@@ -26,8 +49,312 @@ pub struct Args {
     #[clap(short)]
     pub env: Option<Vec<String>>,
 
+    /// crates to add to a rust file's dependencies, e.g. `--crate serde@1`
+    /// merged with the manifest embedded in the file, if any
+    #[clap(long = "crate")]
+    pub crates: Option<Vec<String>>,
+
+    /// run the file against several versions of its runtime, one container each,
+    /// e.g. `--matrix python=3.9,3.12`
+    #[clap(long, conflicts_with_all = ["no_docker", "image"])]
+    pub matrix: Option<Matrix>,
+
+    /// run the program N times on every change and report timing statistics,
+    /// compared to the file's baseline (its first benchmark, or the last `--save-baseline`)
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with = "matrix",
+        value_parser = clap::value_parser!(u64).range(1..).map(|n| n as usize)
+    )]
+    pub bench: Option<usize>,
+
+    /// runs discarded before `--bench` starts measuring
+    #[clap(long, value_name = "W", default_value_t = 1, requires = "bench")]
+    pub warmup: usize,
+
+    /// make each `--bench` result the baseline the next ones are compared to
+    #[clap(long, requires = "bench")]
+    pub save_baseline: bool,
+
     /// do not use docker to run the code
     /// this is useful when you want to run the code on your local machine
     #[clap(long)]
     pub no_docker: bool,
+
+    /// file fed to the program's stdin on every run
+    #[clap(long)]
+    pub stdin: Option<PathBuf>,
+
+    /// show how the output changed since the previous run,
+    /// `compact` replaces the output with the diff once the run ends, `full` prints the diff below it
+    #[clap(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "compact",
+        conflicts_with_all = ["matrix", "bench"]
+    )]
+    pub output_diff: Option<OutputDiff>,
+
+    /// `json` prints one json object per event on stdout instead of the program's output,
+    /// which is then captured into the `run-finished` event
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = Format::Text,
+        conflicts_with_all = ["matrix", "bench", "output_diff"]
+    )]
+    pub format: Format,
+
+    /// listen on this unix socket for json-rpc requests from editors:
+    /// `rerun`, `cancel`, `set_runtime`, `set_docker`, `last_outcome` and `subscribe`
+    #[clap(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+
+    /// run the file once, without watching it, and exit with its exit code,
+    /// 123 when the build fails, 124 on timeout and 125 when runner itself fails
+    #[clap(long, conflicts_with_all = ["socket", "output_diff"])]
+    pub once: bool,
+
+    /// print what running the file would do, the runtime, backend, image, mounts, env
+    /// and every command line, without building, running or writing anything, see `runner explain`
+    #[clap(long, conflicts_with_all = ["matrix", "bench", "socket", "once"])]
+    pub dry_run: bool,
+
+    /// keep the program running as a server: on change the new version is started,
+    /// and the old one stopped once the new one is ready, see `--ready-port` and `--ready-pattern`
+    #[clap(
+        long,
+        conflicts_with_all = ["matrix", "bench", "output_diff", "once", "dry_run", "stdin", "socket"]
+    )]
+    pub serve: bool,
+
+    /// a `--serve` server is ready once this port, of localhost or of its container, accepts connections,
+    /// the old version of a local server is then stopped before the new one starts since they can't share the port
+    #[clap(long, value_name = "PORT", requires = "serve")]
+    pub ready_port: Option<u16>,
+
+    /// a `--serve` server is ready once it prints a line containing this text,
+    /// without `--ready-port` or `--ready-pattern` it's ready as soon as it starts
+    #[clap(long, value_name = "TEXT", requires = "serve")]
+    pub ready_pattern: Option<String>,
+
+    /// seconds a `--serve` server gets to become ready, the previous version keeps serving otherwise
+    #[clap(long, value_name = "SECS", default_value_t = 30.0, requires = "serve", value_parser = positive_secs)]
+    pub ready_timeout: f64,
+
+    /// `HOST[:TARGET]`, publishes port TARGET of a docker container on port HOST,
+    /// with `--serve` HOST is a proxy to the current version that stays up across restarts,
+    /// a local server without TARGET gets a free port in `$PORT` and is found on the port it listens on
+    #[clap(long, value_name = "HOST[:TARGET]", conflicts_with = "matrix")]
+    pub port: Option<Port>,
+
+    /// reload the pages opened through the `--port` proxy of `--serve` after every restart,
+    /// a client is added to the html responses for that
+    #[clap(long, requires = "serve", requires = "port")]
+    pub live_reload: bool,
+
+    /// stop a run that takes longer than this many seconds
+    #[clap(long, value_name = "SECS", value_parser = positive_secs)]
+    pub timeout: Option<f64>,
+
+    /// do not record the runs in the history
+    #[clap(long)]
+    pub no_history: bool,
+
+    /// record the program's output in the history too, it then goes through runner
+    /// and the program doesn't see a terminal, it's always recorded when stdout isn't a terminal
+    #[clap(long, conflicts_with = "no_history")]
+    pub record_output: bool,
+
+    /// arguments passed to the program, e.g. `runner main.py -- --verbose`
+    #[clap(last = true)]
+    pub args: Vec<String>,
+}
+
+impl RunArgs {
+    pub fn readiness(&self) -> Readiness {
+        Readiness {
+            port: self.ready_port,
+            pattern: self.ready_pattern.clone(),
+            timeout: Duration::from_secs_f64(self.ready_timeout),
+        }
+    }
+
+    pub fn run_options(&self) -> anyhow::Result<RunOptions> {
+        Ok(RunOptions {
+            no_docker: self.no_docker,
+            command: self.command.clone(),
+            env: self.env.clone().unwrap_or_default(),
+            docker_image: self.image.as_deref().map(str::parse).transpose()?,
+            crates: self.crates.clone().unwrap_or_default(),
+            args: self.args.clone(),
+            stdin: self.stdin.clone(),
+            capture: self.format == Format::Json,
+            tee: self.format == Format::Text
+                && (self.output_diff.is_some()
+                    || !self.no_history
+                        && (self.record_output || !std::io::stdout().is_terminal())),
+            prefix: None,
+            port: self.port,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// status lines for people
+    Text,
+    /// newline delimited json events for editors and scripts
+    Json,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Commands {
+    /// run a file once and exit with its exit code, see `--once`
+    Run(RunArgs),
+
+    /// show what running a file would do without running it, see `--dry-run`
+    Explain(RunArgs),
+
+    /// show the toolchains and docker images available for every runtime
+    Doctor,
+
+    /// run implementations of the same program in different languages on every change
+    /// and compare their outputs, e.g. `runner compare solve.py solve.go solve.rs`
+    Compare(CompareArgs),
+
+    /// list the runs recorded in the current directory
+    History,
+
+    /// show the source and output of a recorded run
+    Show {
+        /// number of the run, see `runner history`
+        run: usize,
+    },
+
+    /// show how the source and output changed between two recorded runs
+    Diff {
+        /// the earlier run
+        old: usize,
+        /// the later run
+        new: usize,
+    },
+
+    /// replay the recorded runs between a good and a bad one to find the edit that broke it
+    Bisect(BisectArgs),
+
+    /// run the processes of a Procfile or of the `[processes]` table of a runner.toml together,
+    /// restarting each one when its file changes
+    Up(UpArgs),
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct UpArgs {
+    /// a Procfile or runner.toml, the one in the current directory by default
+    pub config: Option<PathBuf>,
+
+    /// do not use docker to run the processes
+    #[clap(long)]
+    pub no_docker: bool,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct CompareArgs {
+    /// files to compare, the outputs are compared to the first one's
+    #[clap(required = true, num_args = 2..)]
+    pub paths: Vec<PathBuf>,
+
+    /// environment variables to pass to every program
+    #[clap(short)]
+    pub env: Option<Vec<String>>,
+
+    /// do not use docker to run the code
+    #[clap(long)]
+    pub no_docker: bool,
+
+    /// file fed to every program's stdin
+    #[clap(long)]
+    pub stdin: Option<PathBuf>,
+
+    /// arguments passed to every program
+    #[clap(last = true)]
+    pub args: Vec<String>,
+}
+
+impl CompareArgs {
+    pub fn run_options(&self) -> RunOptions {
+        RunOptions {
+            no_docker: self.no_docker,
+            env: self.env.clone().unwrap_or_default(),
+            args: self.args.clone(),
+            stdin: self.stdin.clone(),
+            capture: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct BisectArgs {
+    /// a recorded run that behaved as expected
+    #[clap(long)]
+    pub good: usize,
+
+    /// a later recorded run that did not
+    #[clap(long)]
+    pub bad: usize,
+
+    /// a run is good when its output contains this text, instead of when it exits with 0
+    #[clap(long, value_name = "TEXT")]
+    pub output_contains: Option<String>,
+
+    /// a run is good when its output is the same as the good run's, instead of when it exits with 0
+    #[clap(long, conflicts_with = "output_contains")]
+    pub same_output: bool,
+
+    /// environment variables to pass to the program
+    #[clap(short)]
+    pub env: Option<Vec<String>>,
+
+    /// do not use docker to run the code
+    #[clap(long)]
+    pub no_docker: bool,
+
+    /// file fed to the program's stdin
+    #[clap(long)]
+    pub stdin: Option<PathBuf>,
+
+    /// arguments passed to the program
+    #[clap(last = true)]
+    pub args: Vec<String>,
+}
+
+impl BisectArgs {
+    pub fn run_options(&self) -> RunOptions {
+        RunOptions {
+            no_docker: self.no_docker,
+            env: self.env.clone().unwrap_or_default(),
+            args: self.args.clone(),
+            stdin: self.stdin.clone(),
+            capture: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// a number of seconds a `Duration` can hold, above zero
+fn positive_secs(s: &str) -> Result<f64, String> {
+    let secs: f64 = s
+        .parse()
+        .map_err(|_| format!("'{s}' is not a number of seconds"))?;
+
+    if secs > 0.0 && Duration::try_from_secs_f64(secs).is_ok() {
+        Ok(secs)
+    } else {
+        Err(format!(
+            "{s} is not a duration, expected more than 0 seconds"
+        ))
+    }
 }
@@ -1,15 +1,28 @@
-use std::{process::ExitStatus, str::FromStr};
+use std::{
+    fmt,
+    future::Future,
+    io::IsTerminal,
+    process::{Output, Stdio},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
 
-use crate::{command, file_type::FileType, spawn_command};
+use tokio::{process::Command, select, sync::oneshot};
+
+use crate::{
+    command,
+    command::command_line,
+    core::RunOptions,
+    file_type::FileType,
+    usage::{self, ContainerSamples, Usage},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum DockerError {
     #[error("Unsupported docker runtime: {0}")]
     UnsupportedRuntime(String),
 
-    #[error("Docker image does not exist: {0}")]
-    ImageDoesNotExist(String),
-
     #[error("Docker image not installed: {0}")]
     ImageNotInstalled(String),
 
@@ -66,22 +79,208 @@ impl FromStr for DockerImage {
     }
 }
 
-impl ToString for DockerImage {
-    fn to_string(&self) -> String {
-        self.get_image()
+impl fmt::Display for DockerImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_image())
     }
 }
 
 pub async fn pull(image: &str) -> std::result::Result<std::process::Output, DockerError> {
-    Ok(command!("docker", "pull", image).output().await?)
+    let start = Instant::now();
+    let output = command!("docker", "pull", image).output().await?;
+    log::info!(
+        "pulling {image} exited with {} after {:.2?}",
+        output.status,
+        start.elapsed()
+    );
+
+    Ok(output)
 }
 
+/// runs attached to the terminal, the output is only captured with `options.tee`,
+/// the container is removed once it exits
 pub async fn run(
     ft: &FileType,
     filepath: &str,
     docker_command: Option<String>,
     docker_image: Option<DockerImage>,
-) -> std::result::Result<(ExitStatus, String), DockerError> {
+    volumes: &[String],
+    options: &RunOptions,
+) -> std::result::Result<(Output, String, Option<Usage>), DockerError> {
+    let (args, image, name) =
+        run_args(ft, filepath, docker_command, docker_image, volumes, options).await?;
+
+    let mut command = Command::new("docker");
+    command.args(&args).kill_on_drop(true);
+    log::debug!("$ {}", command_line(&command));
+
+    if let Some(stdin) = &options.stdin {
+        command.stdin(std::fs::File::open(stdin)?);
+    }
+
+    if options.tee {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+
+    let start = Instant::now();
+    let child = command.spawn()?;
+    let (output, usage) =
+        sampled(&name, usage::wait_with_output(child, options.tee_prefix())).await;
+    let output = output?;
+    log_exit(&name, &output, start);
+
+    Ok((output, image, usage))
+}
+
+/// like `run`, capturing the output instead of attaching a terminal,
+/// the container is removed once it exits
+pub async fn output(
+    ft: &FileType,
+    filepath: &str,
+    docker_command: Option<String>,
+    docker_image: Option<DockerImage>,
+    volumes: &[String],
+    options: &RunOptions,
+) -> std::result::Result<(Output, String, Option<Usage>), DockerError> {
+    let (args, image, name) =
+        run_args(ft, filepath, docker_command, docker_image, volumes, options).await?;
+
+    let mut command = Command::new("docker");
+    command.args(&args).kill_on_drop(true);
+    log::debug!("$ {}", command_line(&command));
+
+    if let Some(stdin) = &options.stdin {
+        command.stdin(std::fs::File::open(stdin)?);
+    }
+
+    let start = Instant::now();
+    let (output, usage) = sampled(&name, command.output()).await;
+    let output = output?;
+    log_exit(&name, &output, start);
+
+    Ok((output, image, usage))
+}
+
+fn log_exit(name: &str, output: &Output, start: Instant) {
+    log::debug!(
+        "container {name} exited with {} after {:.2?}",
+        output.status,
+        start.elapsed()
+    );
+}
+
+/// the `docker run` command keeping a server running in the background, see `--serve`,
+/// with its image and container name, the container is removed once it exits
+pub async fn serve(
+    ft: &FileType,
+    filepath: &str,
+    docker_command: Option<String>,
+    docker_image: Option<DockerImage>,
+    volumes: &[String],
+    options: &RunOptions,
+) -> std::result::Result<(Command, String, String), DockerError> {
+    // captured runs get `--rm` and no tty, the output is read by the daemon
+    let options = RunOptions {
+        capture: true,
+        ..options.clone()
+    };
+    let (args, image, name) = run_args(
+        ft,
+        filepath,
+        docker_command,
+        docker_image,
+        volumes,
+        &options,
+    )
+    .await?;
+
+    let mut command = Command::new("docker");
+    command.args(&args);
+    log::debug!("$ {}", command_line(&command));
+
+    Ok((command, image, name))
+}
+
+/// samples the usage of the container `name` with `docker stats` until `until` completes
+async fn sampled<T>(name: &str, until: impl Future<Output = T>) -> (T, Option<Usage>) {
+    let (stop, mut stopped) = oneshot::channel::<()>();
+    let sampled_name = name.to_string();
+
+    let sampler = tokio::spawn(async move {
+        let name = sampled_name;
+        let mut samples = ContainerSamples::default();
+
+        loop {
+            select! {
+                _ = &mut stopped => return samples,
+                _ = tokio::time::sleep(Duration::from_millis(500)) => {}
+            }
+
+            // not through `command!` to keep the samples out of the debug output
+            let stats = tokio::process::Command::new("docker")
+                .args(["stats", "--no-stream", "--format"])
+                .arg("{{.MemUsage}}\t{{.CPUPerc}}")
+                .arg(&name)
+                .output()
+                .await;
+
+            if let Some(stats) = stats.ok().filter(|s| s.status.success()) {
+                let sample = String::from_utf8_lossy(&stats.stdout);
+                log::trace!("container {name} uses {}", sample.trim());
+                samples.record(&sample);
+            }
+        }
+    });
+
+    let mut running = Running(Some(name));
+    let result = until.await;
+    running.0 = None;
+
+    let _ = stop.send(());
+
+    let usage = sampler.await.ok().and_then(|samples| samples.usage());
+
+    (result, usage)
+}
+
+/// Kills the container when a cancelled run drops its future,
+/// killing the docker cli would leave it running
+struct Running<'a>(Option<&'a str>);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        if let Some(name) = self.0 {
+            log::debug!("killing container {name}, its run was cancelled");
+            let _ = std::process::Command::new("docker")
+                .args(["kill", name])
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn();
+        }
+    }
+}
+
+/// a container name unique to this run, so `docker stats` can find it
+fn container_name() -> String {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    format!(
+        "runner-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// arguments of `docker run`, the image they use and the name of the container,
+/// once the image is known to be on the machine
+async fn run_args(
+    ft: &FileType,
+    filepath: &str,
+    docker_command: Option<String>,
+    docker_image: Option<DockerImage>,
+    volumes: &[String],
+    options: &RunOptions,
+) -> std::result::Result<(Vec<String>, String, String), DockerError> {
     let image = docker_image
         .or(ft.get_docker_image())
         .ok_or(DockerError::UnsupportedRuntime(ft.to_string()))?;
@@ -91,6 +290,24 @@ pub async fn run(
         return Err(DockerError::ImageNotInstalled(image.to_string()));
     }
 
+    run_command(ft, filepath, docker_command, Some(image), volumes, options)
+}
+
+/// like `run_args`, without checking the image, which is what `run` or `output` would execute
+/// as chosen by `options.capture`, `options.args` are appended to the command run in the container
+/// and `options.env` is set in it
+pub fn run_command(
+    ft: &FileType,
+    filepath: &str,
+    docker_command: Option<String>,
+    docker_image: Option<DockerImage>,
+    volumes: &[String],
+    options: &RunOptions,
+) -> std::result::Result<(Vec<String>, String, String), DockerError> {
+    let image = docker_image
+        .or(ft.get_docker_image())
+        .ok_or(DockerError::UnsupportedRuntime(ft.to_string()))?;
+
     let entrypoint = ft
         .get_docker_entrypoint()
         .ok_or(DockerError::UnsupportedRuntime(ft.to_string()))?;
@@ -109,14 +326,45 @@ pub async fn run(
         .or(ft.get_docker_command())
         .ok_or(DockerError::UnsupportedRuntime(ft.to_string()))?;
 
+    let command = std::iter::once(command)
+        .chain(options.args.iter().map(|a| shell_quote(a)))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // a tty is only attached when runner has one itself, under ci docker
+    // would refuse it, nor when stdin comes from a file
+    let mut flags = vec!["--rm"];
+    if !options.capture || options.stdin.is_some() {
+        flags.push("-i");
+    }
+    if !options.capture && options.stdin.is_none() && std::io::stdin().is_terminal() {
+        flags.push("-t");
+    }
+
     let image = image.to_string();
+    let name = container_name();
 
-    let exit_status =
-        spawn_command!("docker", "run", "-v", &volume, "-it", &image, "sh", "-c", &command)?
-            .wait()
-            .await?;
+    let mut args = vec!["run".to_string(), "--name".to_string(), name.clone()];
+    args.extend(["-v".to_string(), volume]);
+    for v in volumes {
+        args.push("-v".to_string());
+        args.push(v.clone());
+    }
+    if let Some(port) = &options.port {
+        args.extend(["-p".to_string(), port.publish()]);
+    }
+    for (key, value) in options.env_pairs() {
+        args.extend(["-e".to_string(), format!("{key}={value}")]);
+    }
+    args.extend(flags.iter().map(|f| f.to_string()));
+    args.extend([&image, "sh", "-c", &command].map(String::from));
+
+    Ok((args, image, name))
+}
 
-    Ok((exit_status, image))
+/// quotes `arg` for `sh`, `it's` becomes `'it'\''s'`
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
 }
 
 pub async fn image_exists_on_machine(image: &str) -> std::result::Result<bool, DockerError> {
@@ -124,5 +372,45 @@ pub async fn image_exists_on_machine(image: &str) -> std::result::Result<bool, D
         .output()
         .await?;
 
+    log::debug!(
+        "image {image} is {}on the machine",
+        if output.status.success() { "" } else { "not " }
+    );
     Ok(output.status.success())
 }
+
+/// whether the docker cli is installed and its daemon reachable
+pub async fn is_daemon_running() -> bool {
+    let running = command!("docker", "info")
+        .output()
+        .await
+        .is_ok_and(|o| o.status.success());
+
+    log::debug!("docker daemon running: {running}");
+    running
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attached_runs_are_removed_and_only_get_a_tty_from_a_terminal() {
+        let (args, _, _) = run_command(
+            &FileType::Node,
+            "main.js",
+            None,
+            None,
+            &[],
+            &RunOptions::default(),
+        )
+        .unwrap();
+
+        assert!(args.contains(&"--rm".to_string()));
+        assert!(args.contains(&"-i".to_string()));
+        assert_eq!(
+            args.contains(&"-t".to_string()),
+            std::io::stdin().is_terminal()
+        );
+    }
+}
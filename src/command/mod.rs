@@ -1,2 +1,35 @@
+use crate::command::docker::shell_quote;
+use tokio::process::Command;
+
 pub mod docker;
 pub mod prelude;
+
+/// `command` as it would be typed in a shell, `cd`ing to its directory first if it has one
+pub fn command_line(command: &Command) -> String {
+    let command = command.as_std();
+
+    let line = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| quote(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    match command.get_current_dir() {
+        Some(dir) => format!("cd {} && {line}", quote(&dir.to_string_lossy())),
+        None => line,
+    }
+}
+
+/// quotes `arg` only when the shell would split or expand it
+pub fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./:=@%+,".contains(c));
+
+    if plain {
+        arg.to_string()
+    } else {
+        shell_quote(arg)
+    }
+}
@@ -1,6 +1,11 @@
+#[doc(hidden)]
 #[macro_export]
 macro_rules! command {
-    ($command:expr) => (tokio::process::Command::new($command));
+    ($command:expr) => {{
+        let command = tokio::process::Command::new($command);
+        log::debug!("$ {}", $crate::command::command_line(&command));
+        command
+    }};
 
     ($command:expr, $($x:expr),+) => {{
         let mut args;
@@ -14,30 +19,16 @@ macro_rules! command {
             )*
         }
 
-        if cfg!(debug_assertions) {
-            dbg!(&args);
-        }
-
-        tokio::process::Command::new($command)
-        .args(&args.clone())
+        let mut command = tokio::process::Command::new($command);
+        command.args(&args);
+        log::debug!("$ {}", $crate::command::command_line(&command));
+        command
     }};
 }
 
+#[doc(hidden)]
 #[macro_export]
 macro_rules! spawn_command {
-    ($command:expr) => (tokio::process::Command::new($command).spawn());
-    ($command:expr, $($args:expr),*) => {{
-        let mut args;
-
-        #[allow(clippy::vec_init_then_push)]
-        {
-            args = Vec::new();
-            $( args.push($args); )*
-            if cfg!(debug_assertions) {
-                dbg!(&args);
-            }
-        }
-
-        tokio::process::Command::new($command).args(&args.clone()).spawn()
-    }}
+    ($command:expr) => ($crate::command!($command).spawn());
+    ($command:expr, $($args:expr),*) => ($crate::command!($command, $($args),*).spawn());
 }
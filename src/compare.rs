@@ -0,0 +1,223 @@
+use crate::cli::CompareArgs;
+use anyhow::{anyhow, bail};
+use colored::*;
+use notify::{
+    event::{DataChange, ModifyKind},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use runner::{
+    core::{self, RunOutcome, RunStatus},
+    diagnostic::{self, Diagnostic},
+    file_type::FileType,
+    utils::{clear_screen, format_bytes, format_table},
+};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+use tokio::{select, sync::mpsc};
+
+/// watches every file and, on any change, runs them all with the same stdin and arguments
+pub async fn run(args: &CompareArgs) -> anyhow::Result<()> {
+    let mut file_types = vec![];
+    for path in &args.paths {
+        match FileType::try_from(path)? {
+            FileType::Unsupported => bail!("Unsupported file type: '{}'", path.display()),
+            file_type => file_types.push(file_type),
+        }
+    }
+
+    let labels: Vec<String> = args.paths.iter().map(|p| p.display().to_string()).collect();
+    let options = args.run_options();
+
+    let (tx, mut rx) = mpsc::channel(10);
+    let mut watcher = RecommendedWatcher::new(
+        move |result: std::result::Result<Event, notify::Error>| {
+            tx.blocking_send(result).expect("failed to send event");
+        },
+        notify::Config::default(),
+    )?;
+
+    for path in &args.paths {
+        watcher.watch(path, RecursiveMode::NonRecursive)?;
+    }
+
+    clear_screen();
+    eprintln!("🏃 Watching {} for changes...", labels.join(", ").yellow());
+    eprintln!();
+
+    loop {
+        select! {
+            res = rx.recv() => {
+                let event = res.ok_or(anyhow!("Failed to receive event"))??;
+
+                if !matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(DataChange::Content))
+                ) {
+                    continue;
+                }
+
+                clear_screen();
+
+                eprintln!("🏃File Changed!");
+                eprintln!();
+
+                let mut outcomes = vec![];
+                for (path, file_type) in args.paths.iter().zip(&file_types) {
+                    let outcome = match core::run(file_type, path, &options).await {
+                        Ok(outcome) => outcome,
+                        // reported like a failed build so the other files still run
+                        Err(e) => RunOutcome {
+                            status: RunStatus::BuildFailed,
+                            diagnostics: vec![Diagnostic::error(&e.to_string())],
+                            ..Default::default()
+                        },
+                    };
+
+                    outcomes.push(outcome);
+                }
+
+                eprintln!();
+                render(&labels, &outcomes);
+
+                eprintln!("🏃 Watching {} for changes...", labels.join(", ").yellow());
+            }
+
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// prints the output of every run under its label, then a table with one column per run
+/// and whether the outputs agree with the first one's
+pub fn render(labels: &[String], outcomes: &[RunOutcome]) {
+    for (label, outcome) in labels.iter().zip(outcomes) {
+        eprintln!("{}", format!("── {label} ──").bold());
+
+        match outcome.status {
+            RunStatus::BuildFailed => diagnostic::render(&outcome.diagnostics),
+            _ => print!("{}", outcome.output.as_deref().unwrap_or_default()),
+        }
+
+        eprintln!();
+    }
+
+    let base = outcomes.first().and_then(|o| o.output.as_deref());
+
+    let row = |label: &str, cell: &dyn Fn(usize, &RunOutcome) -> String| {
+        std::iter::once(label.to_string())
+            .chain(outcomes.iter().enumerate().map(|(i, o)| cell(i, o)))
+            .collect::<Vec<_>>()
+    };
+
+    let mut rows = vec![std::iter::once(String::new())
+        .chain(labels.iter().cloned())
+        .collect()];
+
+    if outcomes.iter().any(|o| !o.build_duration.is_zero()) {
+        rows.push(row("build", &|_, o| {
+            if o.build_duration.is_zero() {
+                "-".to_string()
+            } else {
+                format!("{:.2?}", o.build_duration)
+            }
+        }));
+    }
+
+    rows.extend([row("run", &|_, o| match o.status {
+        RunStatus::BuildFailed => "-".to_string(),
+        _ => format!("{:.2?}", o.run_duration),
+    })]);
+
+    if outcomes.iter().any(|o| o.usage.is_some()) {
+        rows.push(row("memory", &|_, o| match o.usage {
+            Some(usage) => format_bytes(usage.peak_memory()),
+            None => "-".to_string(),
+        }));
+    }
+
+    rows.extend([
+        row("exit", &|_, o| o.status.to_string()),
+        row("output", &|_, o| match &o.output {
+            Some(output) => output_hash(output),
+            None => "-".to_string(),
+        }),
+        row("diff", &|i, o| match (i, base, &o.output) {
+            (0, _, _) => "-".to_string(),
+            (_, Some(base), Some(output)) => first_diff(base, output),
+            _ => "-".to_string(),
+        }),
+    ]);
+
+    let lines = format_table(&rows);
+
+    eprintln!("{}", lines[0].bold());
+    for line in &lines[1..] {
+        eprintln!("{line}");
+    }
+
+    let differing: Vec<&str> = labels
+        .iter()
+        .zip(outcomes)
+        .skip(1)
+        .filter(|(_, o)| o.output.as_deref() != base || base.is_none())
+        .map(|(label, _)| label.as_str())
+        .collect();
+
+    eprintln!();
+    if differing.is_empty() {
+        eprintln!("{}", "✅ Outputs agree".green());
+    } else {
+        eprintln!(
+            "{}",
+            format!(
+                "❌ Outputs of {} differ from {}",
+                differing.join(", "),
+                labels[0]
+            )
+            .red()
+        );
+    }
+}
+
+/// short hash telling apart runs with different output
+fn output_hash(output: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    output.hash(&mut hasher);
+
+    format!("{:016x}", hasher.finish())[..8].to_string()
+}
+
+/// the first line where `output` differs from `base`
+fn first_diff(base: &str, output: &str) -> String {
+    let mut base_lines = base.lines();
+    let mut lines = output.lines();
+
+    for n in 1.. {
+        match (base_lines.next(), lines.next()) {
+            (None, None) => break,
+            (a, b) if a == b => continue,
+            (a, b) => return format!("line {n}: {} vs {}", quote(a), quote(b)),
+        }
+    }
+
+    // the lines are equal, only line endings can differ
+    if base == output {
+        "same".to_string()
+    } else {
+        "line endings".to_string()
+    }
+}
+
+fn quote(line: Option<&str>) -> String {
+    match line {
+        Some(line) if line.chars().count() > 30 => {
+            format!("{:?}…", line.chars().take(30).collect::<String>())
+        }
+        Some(line) => format!("{line:?}"),
+        None => "EOF".to_string(),
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::bail;
+use clap::ValueEnum;
+use runner::{event::Event, file_type::FileType};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{os::unix::fs::FileTypeExt, path::Path};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{broadcast, mpsc, oneshot},
+};
+
+/// What a client asks the watch loop to do, answered during runs too
+#[derive(Debug)]
+pub enum Request {
+    /// runs the file again once the current run ends
+    Rerun,
+    /// stops the current run
+    Cancel,
+    SetRuntime(FileType),
+    /// `None` toggles it
+    SetDocker(Option<bool>),
+    LastOutcome,
+}
+
+/// A request and where its result goes
+pub struct Call {
+    pub request: Request,
+    reply: oneshot::Sender<Value>,
+}
+
+impl Call {
+    pub fn reply(self, result: Value) {
+        // the client may be gone already
+        let _ = self.reply.send(result);
+    }
+}
+
+/// The control socket of a watch session, see `--socket`
+pub struct Server {
+    calls: mpsc::Receiver<Call>,
+}
+
+impl Server {
+    /// listens on `path`, replacing a stale socket left by a previous session,
+    /// `events` are sent to subscribed clients
+    pub fn bind(path: &Path, events: broadcast::Sender<Event>) -> anyhow::Result<Self> {
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                bail!("'{}' already exists and isn't a socket", path.display());
+            }
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                bail!("another session already listens on '{}'", path.display());
+            }
+
+            log::debug!("removing the stale socket {}", path.display());
+            std::fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        let (calls_tx, calls) = mpsc::channel(16);
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                log::debug!("control client connected");
+                tokio::spawn(serve(stream, calls_tx.clone(), events.clone()));
+            }
+        });
+
+        Ok(Self { calls })
+    }
+}
+
+/// the next call of `server`, never resolves without one
+pub async fn next_call(server: &mut Option<Server>) -> Option<Call> {
+    match server {
+        Some(server) => server.calls.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// A json-rpc 2.0 request, one per line
+#[derive(Debug, Deserialize)]
+struct Message {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+async fn serve(stream: UnixStream, calls: mpsc::Sender<Call>, events: broadcast::Sender<Event>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    // what is written back to the client, responses and event notifications alike
+    let (out_tx, mut out_rx) = mpsc::channel::<String>(64);
+    tokio::spawn(async move {
+        while let Some(line) = out_rx.recv().await {
+            if writer
+                .write_all(format!("{line}\n").as_bytes())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let message: Message = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                let _ = out_tx
+                    .send(error(Value::Null, -32700, &e.to_string()))
+                    .await;
+                continue;
+            }
+        };
+
+        // a request without an id is a notification, it is never answered
+        let id = message.id.clone();
+        log::debug!(
+            "control request {}: {} {}",
+            id.clone().unwrap_or(Value::Null),
+            message.method,
+            message.params
+        );
+
+        let request = match message.method.as_str() {
+            "rerun" => Request::Rerun,
+            "cancel" => Request::Cancel,
+            "last_outcome" => Request::LastOutcome,
+            "set_runtime" => {
+                let runtime = message.params["runtime"].as_str().unwrap_or_default();
+                match FileType::from_str(runtime, true) {
+                    Ok(file_type) => Request::SetRuntime(file_type),
+                    Err(e) => {
+                        if let Some(id) = id {
+                            let _ = out_tx.send(error(id, -32602, &e)).await;
+                        }
+                        continue;
+                    }
+                }
+            }
+            "set_docker" => Request::SetDocker(message.params["enabled"].as_bool()),
+            "subscribe" => {
+                let mut events = events.subscribe();
+                let out = out_tx.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let event = match events.recv().await {
+                            Ok(event) => event,
+                            // a slow client misses events rather than holding the others back
+                            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        };
+
+                        let notification = format!(
+                            r#"{{"jsonrpc":"2.0","method":"event","params":{}}}"#,
+                            event.to_json()
+                        );
+                        if out.send(notification).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                if let Some(id) = id {
+                    let _ = out_tx.send(response(id, Value::Bool(true))).await;
+                }
+                continue;
+            }
+            method => {
+                if let Some(id) = id {
+                    let _ = out_tx
+                        .send(error(id, -32601, &format!("unknown method '{method}'")))
+                        .await;
+                }
+                continue;
+            }
+        };
+
+        let (reply, result) = oneshot::channel();
+        if calls.send(Call { request, reply }).await.is_err() {
+            break;
+        }
+
+        let Some(id) = id else {
+            continue;
+        };
+
+        let out = out_tx.clone();
+        tokio::spawn(async move {
+            if let Ok(result) = result.await {
+                let _ = out.send(response(id, result)).await;
+            }
+        });
+    }
+}
+
+fn response(id: Value, result: Value) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string()
+}
+
+fn error(id: Value, code: i32, message: &str) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }).to_string()
+}
@@ -1,179 +1,934 @@
 use crate::{
-    command::docker::{DockerError, DockerImage},
+    command::{
+        command_line,
+        docker::{DockerError, DockerImage},
+    },
+    daemon::Daemon,
+    diagnostic::Diagnostic,
+    event::{Event, Events},
+    explain::{self, Explanation},
     file_type::FileType,
+    pin::{Pin, Resolution, Tool},
+    proxy::Port,
+    runtime::{go::GoModule, java, python, rust::CargoProject, typescript::TsRuntime},
+    toolchain::which_in,
+    usage::{self, Usage},
+    utils::project_cache_dir,
+};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output, Stdio},
+    time::Duration,
 };
-use std::{path::PathBuf, time::Duration};
 
 use crate::command::docker;
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use tokio::process::Command;
 
 pub const DURATION_ZERO: Duration = Duration::from_secs(0);
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RunStatus {
+    #[default]
+    Success,
+    /// the compile step failed, the program did not run
+    BuildFailed,
+    /// the program exited with a non zero code, `None` when killed by a signal that isn't known
+    Failed(Option<i32>),
+    /// the program was killed by this signal
+    Killed(i32),
+    /// the build and run took longer than the runner's timeout and were stopped
+    TimedOut,
+}
+
+impl From<ExitStatus> for RunStatus {
+    fn from(status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+            return Self::Killed(signal);
+        }
+
+        if status.success() {
+            Self::Success
+        } else {
+            Self::Failed(status.code())
+        }
+    }
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Success => write!(f, "0"),
+            Self::BuildFailed => write!(f, "build failed"),
+            Self::Failed(Some(code)) => write!(f, "{code}"),
+            Self::Failed(None) => write!(f, "signal"),
+            Self::Killed(signal) => write!(f, "signal {signal}"),
+            Self::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunOutcome {
+    pub build_duration: Duration,
+    pub run_duration: Duration,
+    pub status: RunStatus,
+    /// docker image used for the run, if any
+    pub image: Option<String>,
+    /// compiler warnings and errors produced by the build step
+    pub diagnostics: Vec<Diagnostic>,
+    /// stdout and stderr of the program, when captured instead of shown
+    pub output: Option<String>,
+    /// cpu and memory used by the run, when the platform reports it
+    pub usage: Option<Usage>,
+}
+
+/// result of the compile step of compiled runtimes
+#[derive(Debug, Clone)]
+pub struct BuildOutput {
+    pub success: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl BuildOutput {
+    /// runs the compiler capturing its output instead of letting it scroll by
+    pub async fn capture(file_type: &FileType, command: &mut Command) -> anyhow::Result<Self> {
+        if explain::record(command) {
+            return Ok(Self {
+                success: true,
+                diagnostics: vec![],
+            });
+        }
+
+        log::debug!("$ {}", command_line(command));
+        let start = std::time::Instant::now();
+
+        // a cancelled run takes its build down with it
+        let output = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        log::debug!(
+            "{} exited with {} after {:.2?}",
+            command.as_std().get_program().to_string_lossy(),
+            output.status,
+            start.elapsed()
+        );
+
+        // tsc reports on stdout, everything else on stderr
+        let text = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        Ok(Self {
+            success: output.status.success(),
+            diagnostics: Diagnostic::from_build(file_type, &text, output.status.success()),
+        })
+    }
+}
+
+/// How `run` runs a file, mirrors the command line flags
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub no_docker: bool,
+    /// replaces the runtime's command, see `--command`
+    pub command: Option<String>,
+    /// `KEY=VALUE` variables passed to the program
+    pub env: Vec<String>,
+    pub docker_image: Option<DockerImage>,
+    /// `name@version` crates added to rust files
+    pub crates: Vec<String>,
+    /// arguments passed to the program
+    pub args: Vec<String>,
+    /// file fed to the program's stdin
+    pub stdin: Option<PathBuf>,
+    /// captures the program's output into `RunOutcome::output` instead of showing it
+    pub capture: bool,
+    /// shows the program's output as it runs while also capturing it into `RunOutcome::output`
+    pub tee: bool,
+    /// printed before every line `tee` or a started server shows, see `runner up`
+    pub prefix: Option<String>,
+    /// port of the container published on the host, see `--port`
+    pub port: Option<Port>,
+}
+
+impl RunOptions {
+    /// the prefix of the lines `tee` shows, `None` without `tee`
+    pub fn tee_prefix(&self) -> Option<&str> {
+        self.tee.then(|| self.prefix.as_deref().unwrap_or_default())
+    }
+
+    /// `env` split into keys and values
+    pub fn env_pairs(&self) -> Vec<(String, String)> {
+        self.env
+            .iter()
+            .map(|e| {
+                let (key, value) = e.split_once('=').unwrap_or((e, ""));
+                (key.to_string(), value.to_string())
+            })
+            .collect()
+    }
+}
+
+/// A file ready to run: built if needed, with its image pulled when it runs in docker
+pub struct Prepared {
+    target: Target,
+    options: RunOptions,
+    build_duration: Duration,
+    diagnostics: Vec<Diagnostic>,
+}
+
+enum Target {
+    Docker {
+        file_type: FileType,
+        path: PathBuf,
+        setup: DockerSetup,
+    },
+    Local(Command),
+    /// the build failed, there is nothing to run
+    None,
+}
+
+impl Prepared {
+    /// runs the program once, without building it again
+    pub async fn execute(&self) -> anyhow::Result<RunOutcome> {
+        let mut outcome = match &self.target {
+            Target::Docker {
+                file_type,
+                path,
+                setup,
+            } => run_in_docker(file_type, path, setup, &self.options).await?,
+            Target::Local(command) => execute_local(clone_command(command), &self.options).await?,
+            Target::None => RunOutcome {
+                status: RunStatus::BuildFailed,
+                ..Default::default()
+            },
+        };
+
+        outcome.build_duration = self.build_duration;
+        outcome.diagnostics = self.diagnostics.clone();
+
+        Ok(outcome)
+    }
+
+    /// whether `execute` only reports the failed build
+    pub fn build_failed(&self) -> bool {
+        matches!(self.target, Target::None)
+    }
+
+    pub fn build_duration(&self) -> Duration {
+        self.build_duration
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// starts the program in the background instead of waiting for it to exit,
+    /// its output is scanned for `pattern`, see `Daemon::wait_ready`
+    pub async fn spawn(&self, pattern: Option<String>) -> anyhow::Result<Daemon> {
+        match &self.target {
+            Target::Docker {
+                file_type,
+                path,
+                setup,
+            } => {
+                let (command, image, name) = docker::serve(
+                    file_type,
+                    path.to_str().unwrap_or_default(),
+                    setup.command.clone(),
+                    setup.image.clone(),
+                    &setup.volumes,
+                    &self.options,
+                )
+                .await?;
+
+                let prefix = self.options.prefix.clone();
+                Daemon::start(command, Some(name), Some(image), pattern, prefix)
+            }
+            Target::Local(command) => Daemon::start(
+                clone_command(command),
+                None,
+                None,
+                pattern,
+                self.options.prefix.clone(),
+            ),
+            Target::None => Err(anyhow!("the build failed, there is nothing to start")),
+        }
+    }
+
+    /// the command running the program locally, `None` in docker or when the build failed
+    pub fn command(&self) -> Option<&Command> {
+        match &self.target {
+            Target::Local(command) => Some(command),
+            _ => None,
+        }
+    }
+}
+
+/// Runs a file the way the `runner` command does, for tools that embed it
+///
+/// Built up from the path of the file, the runtime is inferred from its extension
+/// unless one is given, and it runs in docker unless `docker(false)` is set.
+/// What happens during the run is sent to the receivers of `subscribe` as [`Event`]s.
+#[derive(Debug, Clone)]
+pub struct Runner {
+    path: PathBuf,
+    file_type: Option<FileType>,
+    options: RunOptions,
+    timeout: Option<Duration>,
+    events: Events,
+}
+
+impl Runner {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file_type: None,
+            options: RunOptions::default(),
+            timeout: None,
+            events: Events::new(false),
+        }
+    }
+
+    /// runs the file with this runtime instead of the one of its extension
+    pub fn runtime(mut self, file_type: FileType) -> Self {
+        self.file_type = Some(file_type);
+        self
+    }
+
+    /// replaces every option set so far, for callers that already have them, like the cli
+    pub fn options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// replaces the runtime's command, see `--command`
+    pub fn command(mut self, command: impl Into<String>) -> Self {
+        self.options.command = Some(command.into());
+        self
+    }
+
+    /// sets `key` in the program's environment, in docker too
+    pub fn env(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.options
+            .env
+            .push(format!("{}={}", key.as_ref(), value.as_ref()));
+        self
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.options.args.push(arg.into());
+        self
+    }
+
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.options.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// whether to run in docker when the runtime has an image, on by default
+    pub fn docker(mut self, enabled: bool) -> Self {
+        self.options.no_docker = !enabled;
+        self
+    }
+
+    /// the image to run in instead of the runtime's or the pinned one
+    pub fn image(mut self, image: DockerImage) -> Self {
+        self.options.docker_image = Some(image);
+        self
+    }
+
+    /// `name@version` crates added to a rust file
+    pub fn crates(mut self, crates: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.options
+            .crates
+            .extend(crates.into_iter().map(Into::into));
+        self
+    }
+
+    /// file fed to the program's stdin
+    pub fn stdin(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.stdin = Some(path.into());
+        self
+    }
+
+    /// publishes `port` of the container on the host, docker runs only
+    pub fn port(mut self, port: Port) -> Self {
+        self.options.port = Some(port);
+        self
+    }
+
+    /// captures the program's output into `RunOutcome::output` instead of showing it
+    pub fn capture(mut self) -> Self {
+        self.options.capture = true;
+        self
+    }
+
+    /// stops the build and run once they take longer than `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// sends the events to `events` instead of to the runner's own channel
+    pub fn events(mut self, events: Events) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// the events of the runs started after subscribing
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// the runtime the file runs with
+    pub fn file_type(&self) -> anyhow::Result<FileType> {
+        match &self.file_type {
+            Some(file_type) => Ok(file_type.clone()),
+            None => FileType::try_from(&self.path),
+        }
+    }
+
+    /// what `run` would do, without building, running or writing anything
+    pub async fn explain(&self) -> anyhow::Result<Explanation> {
+        let reason = match &self.file_type {
+            Some(_) => "set explicitly".to_string(),
+            None => explain::detection_reason(&self.path),
+        };
+
+        explain::explain(&self.file_type()?, reason, &self.path, &self.options).await
+    }
+
+    /// builds the file if needed and runs it once
+    pub async fn run(&self) -> anyhow::Result<RunOutcome> {
+        let file_type = self.file_type()?;
+        log::info!("running {} as {file_type}", self.path.display());
+
+        let run = async {
+            self.events.emit(Event::BuildStarted);
+            let prepared = prepare(&file_type, &self.path, &self.options).await?;
+            self.events.emit(Event::build_finished(
+                !prepared.build_failed(),
+                prepared.build_duration(),
+                prepared.diagnostics(),
+            ));
+
+            prepared.execute().await
+        };
+
+        let outcome = match self.timeout {
+            Some(timeout) => {
+                tokio::time::timeout(timeout, run)
+                    .await
+                    .unwrap_or(Ok(RunOutcome {
+                        status: RunStatus::TimedOut,
+                        run_duration: timeout,
+                        ..Default::default()
+                    }))?
+            }
+            None => run.await?,
+        };
+
+        log::info!(
+            "{} finished with {} after {:.2?}",
+            self.path.display(),
+            outcome.status,
+            outcome.build_duration + outcome.run_duration
+        );
+
+        self.events.emit(Event::run_finished(&outcome));
+        if let Some(image) = &outcome.image {
+            self.events.emit(Event::ImageUsed {
+                image: image.clone(),
+            });
+        }
+
+        Ok(outcome)
+    }
+}
+
 pub async fn run(
     file_type: &FileType,
-    no_docker: bool,
-    command: Option<String>,
-    env: Option<Vec<String>>,
-    path: &PathBuf,
-    docker_image: Option<DockerImage>,
-) -> anyhow::Result<(Duration, Duration, Option<String>)> {
-    let passed_env: Vec<(String, String)> = env
-        .clone()
-        .unwrap_or_default()
-        .iter()
-        .map(|e| {
-            let mut parts = e.split('=');
-            (
-                parts.next().unwrap().to_string(),
-                parts.next().unwrap().to_string(),
-            )
-        })
-        .collect();
+    path: &Path,
+    options: &RunOptions,
+) -> anyhow::Result<RunOutcome> {
+    prepare(file_type, path, options).await?.execute().await
+}
+
+/// resolves how `path` runs, building it when the runtime has a build step
+pub async fn prepare(
+    file_type: &FileType,
+    path: &Path,
+    options: &RunOptions,
+) -> anyhow::Result<Prepared> {
+    let mut passed_env = options.env_pairs();
+
+    let prepared = |target, build_duration, diagnostics| Prepared {
+        target,
+        options: options.clone(),
+        build_duration,
+        diagnostics,
+    };
 
-    if !no_docker {
+    if !options.no_docker {
         match file_type.get_docker_image() {
-            Some(_) => {
-                let run_start = std::time::Instant::now();
+            Some(default_image) => {
+                log::debug!("{file_type} runs in docker, {default_image} unless overridden");
 
-                match docker::run(
+                let setup = docker_setup(
                     file_type,
-                    path.to_str().unwrap(),
-                    command.clone(),
-                    docker_image.clone(),
+                    path,
+                    options.command.clone(),
+                    options.docker_image.clone(),
+                    &options.crates,
                 )
-                .await
-                {
-                    Ok((_, image)) => {
-                        let run_elapsed = run_start.elapsed();
-                        return Ok((DURATION_ZERO, run_elapsed, Some(image)));
-                    }
-                    Err(DockerError::ImageNotInstalled(image)) => {
-                        eprintln!("Image not installed: '{}'", image);
-
-                        if inquire::prompt_confirmation("Would you like to install it?")? {
-                            let mut s = spinners::Spinner::new(
-                                spinners::Spinners::Dots,
-                                "Pulling image...".into(),
-                            );
-                            let result = docker::pull(&image).await?;
-
-                            if result.status.success() {
-                                s.stop_with_symbol("✔");
-
-                                let run_start = std::time::Instant::now();
-                                let (_, image) = docker::run(
-                                    file_type,
-                                    path.to_str().unwrap(),
-                                    command.clone(),
-                                    docker_image,
-                                )
-                                .await?;
-                                let run_elapsed = run_start.elapsed();
-                                return Ok((DURATION_ZERO, run_elapsed, Some(image)));
-                            } else {
-                                s.stop_with_symbol("✖");
-                                return Err(anyhow::anyhow!(
-                                    "Failed to pull image: '{}'\n {}",
-                                    image,
-                                    String::from_utf8(result.stderr).unwrap()
-                                ));
-                            }
-                        }
+                .await?;
+
+                let image = setup.image.clone().unwrap_or(default_image).to_string();
+                log::debug!(
+                    "docker image {image}, volumes {:?}, command {:?}",
+                    setup.volumes,
+                    setup.command
+                );
+                let docker = Target::Docker {
+                    file_type: file_type.clone(),
+                    path: path.to_path_buf(),
+                    setup,
+                };
+
+                if docker::image_exists_on_machine(&image).await? {
+                    return Ok(prepared(docker, DURATION_ZERO, vec![]));
+                }
+
+                eprintln!("Image not installed: '{}'", image);
+
+                if inquire::prompt_confirmation("Would you like to install it?")? {
+                    let mut s =
+                        spinners::Spinner::new(spinners::Spinners::Dots, "Pulling image...".into());
+                    let result = docker::pull(&image).await?;
+
+                    if result.status.success() {
+                        s.stop_with_symbol("✔");
+
+                        return Ok(prepared(docker, DURATION_ZERO, vec![]));
+                    } else {
+                        s.stop_with_symbol("✖");
+                        return Err(anyhow::anyhow!(
+                            "Failed to pull image: '{}'\n {}",
+                            image,
+                            String::from_utf8(result.stderr).unwrap()
+                        ));
                     }
-                    Err(e) => return Err(anyhow::anyhow!(e)),
                 }
             }
             None => {
                 eprintln!("Running without docker... ");
-                eprintln!("Unsupported docker runtime: '{}'", file_type.to_string());
+                eprintln!("Unsupported docker runtime: '{}'", file_type);
             }
         }
     }
 
-    match command.clone() {
-        Some(c) => {
-            let run_start = std::time::Instant::now();
-
-            if c.contains(' ') {
-                let mut parts = c.split_whitespace();
-                let mut command = Command::new(parts.next().unwrap());
-
-                command
-                    .envs(passed_env.iter().cloned())
-                    .args(parts)
-                    .arg(path)
-                    .spawn()?
-                    .wait()
-                    .await?;
-            } else {
-                let mut command = Command::new(c);
-                command
-                    .envs(passed_env.iter().cloned())
-                    .arg(path)
-                    .spawn()?
-                    .wait()
-                    .await?;
-            }
+    log::debug!("{file_type} runs locally");
+    let pinned_bin = use_pinned_toolchain(file_type, path, &mut passed_env).await?;
 
-            Ok((DURATION_ZERO, run_start.elapsed(), None))
+    let mut build_duration = DURATION_ZERO;
+    let mut diagnostics = vec![];
+
+    let mut command = match &options.command {
+        Some(c) => {
+            let mut parts = c.split_whitespace();
+            let mut command = Command::new(parts.next().unwrap_or_default());
+            command.args(parts).arg(path);
+            command
         }
         None => {
-            if !file_type.is_available().await? {
+            if pinned_bin.is_none() && !file_type.is_available().await? {
+                let min_version = file_type
+                    .get_min_version()
+                    .map(|v| format!(" (>= {v})"))
+                    .unwrap_or_default();
+
                 return Err(anyhow!(
-                    "cannot find the required command: '{}'",
-                    file_type.get_command_name().await?
+                    "cannot find the required command: '{}'{}",
+                    file_type.get_command_name().await?,
+                    min_version
                 ));
             }
 
-            if matches!(file_type, FileType::Rust) {
-                let stem = path.file_stem().unwrap().to_str().unwrap();
-
-                let build_start = std::time::Instant::now();
-                Command::new(file_type.get_command_name().await?)
-                    .envs(passed_env.iter().cloned())
-                    .arg(path)
-                    .arg("-o")
-                    .arg(&format!("/tmp/{}-runner-build", stem))
-                    .spawn()?
-                    .wait()
-                    .await?;
-                let build_elapsed = build_start.elapsed();
-
-                let run_start = std::time::Instant::now();
-                Command::new(&format!("/tmp/{}-runner-build", stem))
-                    .envs(passed_env.iter().cloned())
-                    .spawn()?
-                    .wait()
-                    .await?;
-                let run_elapsed = run_start.elapsed();
-
-                return Ok((build_elapsed, run_elapsed, None));
+            if matches!(file_type, FileType::Unsupported) {
+                return Err(anyhow!("Unsupported file type: '.{:?}'", path.extension()));
             }
 
-            let mut command = match file_type {
-                FileType::Unsupported => {
-                    return Err(anyhow!("Unsupported file type: '.{:?}'", path.extension()))
-                }
-                _ => Command::new(file_type.get_command_name().await?),
+            let command_name = match pinned_bin.as_ref().and_then(|dir| {
+                file_type
+                    .get_binaries()
+                    .iter()
+                    .find_map(|binary| which_in(binary, [dir.clone()]))
+            }) {
+                Some(binary) => binary.display().to_string(),
+                None => file_type.get_command_name().await?,
             };
 
-            let arguments = match file_type {
-                FileType::Go => vec!["run"],
-                FileType::Typescript => vec!["run"],
-                _ => vec![],
+            match build(file_type, path, &options.crates, &passed_env).await? {
+                Some((build, elapsed, command)) => {
+                    build_duration = elapsed;
+                    diagnostics = build.diagnostics;
+
+                    if !build.success {
+                        return Ok(prepared(Target::None, build_duration, diagnostics));
+                    }
+
+                    command
+                }
+                None => match file_type {
+                    FileType::Typescript => TsRuntime::detect()
+                        .await
+                        .and_then(|ts| ts.command(path))
+                        .ok_or(anyhow!("cannot find a typescript runtime"))?,
+                    FileType::Python | FileType::Python3 => python::command(path, &command_name),
+                    // single-file source launch, see `build` for older JDKs
+                    FileType::Java => {
+                        let mut command = Command::new("java");
+                        command.arg(path);
+                        command
+                    }
+                    _ => {
+                        let mut command = Command::new(&command_name);
+                        command.arg(path);
+                        command
+                    }
+                },
+            }
+        }
+    };
+
+    command.envs(passed_env.iter().cloned()).args(&options.args);
+
+    Ok(prepared(
+        Target::Local(command),
+        build_duration,
+        diagnostics,
+    ))
+}
+
+/// runs a local program, feeding it `options.stdin` and capturing its output if asked
+async fn execute_local(mut command: Command, options: &RunOptions) -> anyhow::Result<RunOutcome> {
+    match &options.stdin {
+        Some(stdin) => {
+            let file = std::fs::File::open(stdin)
+                .with_context(|| format!("unable to read '{}'", stdin.display()))?;
+            command.stdin(file);
+        }
+        None if options.capture => {
+            command.stdin(Stdio::null());
+        }
+        None => {}
+    }
+
+    let run_start = std::time::Instant::now();
+
+    let captured = options.capture || options.tee;
+    if captured {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+
+    log::debug!("$ {}", command_line(&command));
+    let (output, usage) = usage::wait(&mut command, options.tee_prefix()).await?;
+    log::debug!(
+        "{} exited with {} after {:.2?}",
+        command.as_std().get_program().to_string_lossy(),
+        output.status,
+        run_start.elapsed()
+    );
+
+    Ok(RunOutcome {
+        run_duration: run_start.elapsed(),
+        status: output.status.into(),
+        output: captured.then(|| output_text(&output)),
+        usage,
+        ..Default::default()
+    })
+}
+
+/// a fresh copy of `command`, which can only be spawned once
+fn clone_command(command: &Command) -> Command {
+    let command = command.as_std();
+    let mut clone = Command::new(command.get_program());
+
+    clone.args(command.get_args());
+
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => clone.env(key, value),
+            None => clone.env_remove(key),
+        };
+    }
+
+    if let Some(dir) = command.get_current_dir() {
+        clone.current_dir(dir);
+    }
+
+    clone
+}
+
+/// runs in docker, attached to the terminal, capturing the output or both
+pub async fn run_in_docker(
+    file_type: &FileType,
+    path: &Path,
+    setup: &DockerSetup,
+    options: &RunOptions,
+) -> Result<RunOutcome, DockerError> {
+    let filepath = path.to_str().unwrap_or_default();
+    let run_start = std::time::Instant::now();
+
+    // a teed capture streams the output, without a terminal attached
+    let (output, image, usage) = if options.capture && !options.tee {
+        docker::output(
+            file_type,
+            filepath,
+            setup.command.clone(),
+            setup.image.clone(),
+            &setup.volumes,
+            options,
+        )
+        .await?
+    } else {
+        docker::run(
+            file_type,
+            filepath,
+            setup.command.clone(),
+            setup.image.clone(),
+            &setup.volumes,
+            options,
+        )
+        .await?
+    };
+
+    Ok(RunOutcome {
+        run_duration: run_start.elapsed(),
+        status: output.status.into(),
+        image: Some(image),
+        output: (options.capture || options.tee).then(|| output_text(&output)),
+        usage,
+        ..Default::default()
+    })
+}
+
+/// stdout followed by stderr
+pub fn output_text(output: &Output) -> String {
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+/// How a file runs inside docker
+#[derive(Debug, Clone, Default)]
+pub struct DockerSetup {
+    /// `None` for the default image of the file type
+    pub image: Option<DockerImage>,
+    /// `None` for the default command of the file type
+    pub command: Option<String>,
+    /// extra `host:container` volumes, besides the file itself
+    pub volumes: Vec<String>,
+}
+
+/// the image, command and volumes running `path` in docker,
+/// `docker_image` and `command` are the user's overrides
+pub async fn docker_setup(
+    file_type: &FileType,
+    path: &Path,
+    command: Option<String>,
+    docker_image: Option<DockerImage>,
+    crates: &[String],
+) -> anyhow::Result<DockerSetup> {
+    let pinned_image = |tool: Option<Tool>| {
+        tool.and_then(|tool| Pin::find(tool, path))
+            .and_then(|pin| pin.docker_image())
+    };
+
+    let mut image = docker_image.or_else(|| pinned_image(file_type.pin_tool()));
+
+    let (volumes, command) = match file_type {
+        FileType::Rust => (
+            CargoProject::prepare(path, crates).await?.docker_volumes(),
+            command,
+        ),
+        FileType::Go => {
+            let module = GoModule::resolve(path).await?;
+            (
+                module.docker_volumes(),
+                command.or(Some(module.docker_command())),
+            )
+        }
+        FileType::Typescript => {
+            // mirror the runtime that would be used locally
+            let ts = TsRuntime::detect().await.unwrap_or(TsRuntime::Bun);
+            let entrypoint = file_type.get_docker_entrypoint().unwrap_or_default();
+
+            image = image
+                .or_else(|| pinned_image(Some(ts.pin_tool())))
+                .or(Some(ts.docker_image()));
+            (
+                TsRuntime::docker_volumes(path),
+                command.or(Some(ts.docker_command(path, &entrypoint))),
+            )
+        }
+        FileType::Java => {
+            let entrypoint = file_type.get_docker_entrypoint().unwrap_or_default();
+            (
+                vec![],
+                command.or(Some(java::docker_command(path, &entrypoint))),
+            )
+        }
+        _ => (vec![], command),
+    };
+
+    Ok(DockerSetup {
+        image,
+        command,
+        volumes,
+    })
+}
+
+/// puts the toolchain pinned for `path` (`.nvmrc`, `.tool-versions`, ...) first on the `PATH`
+/// of the spawned commands, returning its `bin` directory when it's not already on the `PATH`
+async fn use_pinned_toolchain(
+    file_type: &FileType,
+    path: &Path,
+    env: &mut Vec<(String, String)>,
+) -> anyhow::Result<Option<PathBuf>> {
+    let tool = match file_type {
+        FileType::Typescript => TsRuntime::detect().await.map(|ts| ts.pin_tool()),
+        _ => file_type.pin_tool(),
+    };
+
+    let Some(pin) = tool.and_then(|tool| Pin::find(tool, path)) else {
+        return Ok(None);
+    };
+
+    let resolution = pin.resolve().await;
+    log::debug!(
+        "{} {} pinned in {}: {resolution:?}",
+        pin.tool,
+        pin.version,
+        pin.source.display()
+    );
+
+    match resolution {
+        Resolution::Path => Ok(None),
+        Resolution::Installed(bin) => {
+            let path = env::var_os("PATH").unwrap_or_default();
+            let path =
+                env::join_paths(std::iter::once(bin.clone()).chain(env::split_paths(&path)))?;
+
+            env.push(("PATH".to_string(), path.to_string_lossy().to_string()));
+            Ok(Some(bin))
+        }
+        Resolution::Missing(active) => {
+            let fallback = match active {
+                Some(version) => format!(", running {version}"),
+                None => String::new(),
             };
 
-            let run_start = std::time::Instant::now();
-            command
-                .envs(passed_env.iter().cloned())
-                .args(arguments)
-                .arg(path)
-                .spawn()?
-                .wait()
-                .await?;
+            eprintln!(
+                "⚠️ {} {} is pinned in {} but isn't installed{}",
+                pin.tool,
+                pin.version.yellow(),
+                pin.source.display(),
+                fallback
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// compiles `path` for runtimes with a separate build step,
+/// returning the build result, its duration and the command that runs the program
+async fn build(
+    file_type: &FileType,
+    path: &Path,
+    crates: &[String],
+    env: &[(String, String)],
+) -> anyhow::Result<Option<(BuildOutput, Duration, Command)>> {
+    let compiled = match file_type {
+        FileType::Rust | FileType::C | FileType::Cpp | FileType::Go => true,
+        FileType::Typescript => TsRuntime::detect().await == Some(TsRuntime::Tsc),
+        FileType::Java => !java::supports_source_launch().await,
+        _ => false,
+    };
+
+    if !compiled {
+        return Ok(None);
+    }
+
+    let mut s = (!explain::is_dry_run())
+        .then(|| spinners::Spinner::new(spinners::Spinners::Dots, "Building...".into()));
+    let build_start = std::time::Instant::now();
+
+    let (build, command) = match file_type {
+        FileType::Rust => {
+            let project = CargoProject::prepare(path, crates).await?;
+            let build = project.build(env).await?;
+
+            (build, Command::new(project.binary_path()))
+        }
+        FileType::Go => {
+            let module = GoModule::resolve(path).await?;
+            let out = project_cache_dir("bin", path)?.join("main");
+            let build = module.build(env, &out).await?;
 
-            Ok((DURATION_ZERO, run_start.elapsed(), None))
+            (build, Command::new(out))
         }
+        FileType::Typescript => TsRuntime::compile(path, env).await?,
+        FileType::Java => java::compile(path, env).await?,
+        _ => {
+            let out = project_cache_dir("bin", path)?.join("main");
+
+            let build = BuildOutput::capture(
+                file_type,
+                Command::new(file_type.get_command_name().await?)
+                    .envs(env.iter().cloned())
+                    .arg("-o")
+                    .arg(&out)
+                    .arg(path),
+            )
+            .await?;
+
+            (build, Command::new(out))
+        }
+    };
+
+    if let Some(s) = s.as_mut() {
+        s.stop_with_symbol(if build.success { "✔" } else { "✖" });
+    }
+
+    Ok(Some((build, build_start.elapsed(), command)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runner_env_reaches_the_container() {
+        let runner = Runner::new("main.js").env("KEY", "value");
+
+        let (args, _, _) =
+            docker::run_command(&FileType::Node, "main.js", None, None, &[], &runner.options)
+                .unwrap();
+
+        assert!(args.windows(2).any(|w| w == ["-e", "KEY=value"]));
     }
 }
@@ -0,0 +1,347 @@
+use crate::proxy::Port;
+use anyhow::{anyhow, bail};
+use std::{
+    net::SocketAddr,
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    net::TcpStream,
+    process::{Child, Command},
+    select,
+    sync::watch,
+};
+
+/// how long a server gets to exit once asked to stop, before it's killed
+pub const GRACE: Duration = Duration::from_secs(5);
+
+/// How `--serve` tells that a started server is ready to take requests
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    /// the server accepts connections on this port, of localhost or of its container
+    pub port: Option<u16>,
+    /// the server printed a line containing this text
+    pub pattern: Option<String>,
+    /// how long the server gets to become ready
+    pub timeout: Duration,
+}
+
+/// A program kept running in the background, its output forwarded to runner's
+pub struct Daemon {
+    child: Child,
+    /// the container it runs in, stopped with `docker stop`
+    container: Option<String>,
+    /// docker image it runs in, if any
+    pub image: Option<String>,
+    /// becomes true once the output contains the ready pattern
+    printed: watch::Receiver<bool>,
+}
+
+impl Daemon {
+    /// spawns `command`, whose output is scanned for `pattern` while it's forwarded,
+    /// every line after `prefix`
+    pub fn start(
+        mut command: Command,
+        container: Option<String>,
+        image: Option<String>,
+        pattern: Option<String>,
+        prefix: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        log::debug!("started process {:?}", child.id());
+
+        let (found, printed) = watch::channel(false);
+        let prefix = prefix.unwrap_or_default();
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(forward(
+                stdout,
+                false,
+                prefix.clone(),
+                pattern.clone(),
+                found.clone(),
+            ));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(forward(stderr, true, prefix, pattern, found));
+        }
+
+        Ok(Self {
+            child,
+            container,
+            image,
+            printed,
+        })
+    }
+
+    /// waits until the server is ready, fails when it exits or misses the readiness timeout,
+    /// behind a proxy a local server is probed at `upstream`, its own address, since the proxy holds the ready port
+    pub async fn wait_ready(
+        &mut self,
+        readiness: &Readiness,
+        upstream: Option<SocketAddr>,
+    ) -> anyhow::Result<()> {
+        let printed = &mut self.printed;
+        let container = self.container.as_deref();
+        let upstream = upstream.filter(|_| container.is_none());
+        let ready = async {
+            // the output closing doesn't make it ready, the exit is reported instead
+            if readiness.pattern.is_some() && printed.wait_for(|printed| *printed).await.is_err() {
+                std::future::pending::<()>().await;
+            }
+
+            if let Some(port) = readiness.port {
+                while !match upstream {
+                    Some(address) => TcpStream::connect(address).await.is_ok(),
+                    None => accepts(container, port).await,
+                } {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        };
+
+        select! {
+            _ = ready => Ok(()),
+            status = self.child.wait() => bail!("the server exited with {} before it was ready", status?),
+            _ = tokio::time::sleep(readiness.timeout) => {
+                bail!("the server wasn't ready after {:?}", readiness.timeout)
+            }
+        }
+    }
+
+    /// where the server listens for `port`, waiting until it does:
+    /// the port docker published the container's on, the target port of a local server,
+    /// or the first port a local server without one listens on
+    pub async fn address(&mut self, port: &Port, timeout: Duration) -> anyhow::Result<SocketAddr> {
+        let container = self.container.clone();
+        let pid = self.child.id();
+        let listening = async {
+            loop {
+                let address = match (&container, port.target) {
+                    (Some(name), _) => published(name, port.container()).await,
+                    (None, Some(target)) => TcpStream::connect(("127.0.0.1", target))
+                        .await
+                        .ok()
+                        .map(|_| SocketAddr::from(([127, 0, 0, 1], target))),
+                    (None, None) => pid
+                        .and_then(|pid| listening_ports(pid).first().copied())
+                        .map(|port| SocketAddr::from(([127, 0, 0, 1], port))),
+                };
+
+                if let Some(address) = address {
+                    return address;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+
+        select! {
+            address = listening => Ok(address),
+            status = self.child.wait() => bail!("the server exited with {} before it listened on a port", status?),
+            _ = tokio::time::sleep(timeout) => Err(anyhow!("the server didn't listen on a port after {timeout:?}")),
+        }
+    }
+
+    /// asks the server to exit, killing it when it's still running after `GRACE`
+    pub async fn stop(mut self) {
+        if let Some(name) = &self.container {
+            log::debug!("stopping container {name}");
+            let grace = GRACE.as_secs().to_string();
+            let _ = crate::command!("docker", "stop", "-t", &grace, name)
+                .output()
+                .await;
+        } else {
+            terminate(&self.child);
+        }
+
+        if tokio::time::timeout(GRACE, self.child.wait())
+            .await
+            .is_err()
+        {
+            log::debug!("process {:?} ignored the stop, killing it", self.child.id());
+            let _ = self.child.kill().await;
+        }
+    }
+}
+
+/// resolves when the server of `daemon` exits, never without one
+pub async fn exited(daemon: &mut Option<Daemon>) -> std::io::Result<ExitStatus> {
+    match daemon {
+        Some(daemon) => daemon.child.wait().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// the address of localhost docker published `port` of the container `name` on
+async fn published(name: &str, port: u16) -> Option<SocketAddr> {
+    let port = port.to_string();
+    let output = crate::command!("docker", "port", name, &port)
+        .output()
+        .await
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.trim().parse().ok())
+}
+
+/// ports the process `pid` and its children listen on, read from `/proc`
+#[cfg(target_os = "linux")]
+pub fn listening_ports(pid: u32) -> Vec<u16> {
+    use std::{collections::HashSet, fs};
+
+    // servers started through a wrapper (`npm start`, `uv run`) listen in a child process
+    let mut pids = vec![pid];
+    let parents: Vec<(u32, u32)> = fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let stat = fs::read_to_string(entry.path().join("stat")).ok()?;
+            // the name in parentheses can contain spaces, the parent pid is the second field after it
+            let ppid = stat
+                .rsplit_once(')')?
+                .1
+                .split_whitespace()
+                .nth(1)?
+                .parse()
+                .ok()?;
+            Some((pid, ppid))
+        })
+        .collect();
+
+    let mut i = 0;
+    while i < pids.len() {
+        let parent = pids[i];
+        pids.extend(
+            parents
+                .iter()
+                .filter(|(_, p)| *p == parent)
+                .map(|(c, _)| *c),
+        );
+        i += 1;
+    }
+
+    let sockets: HashSet<String> = pids
+        .iter()
+        .filter_map(|pid| fs::read_dir(format!("/proc/{pid}/fd")).ok())
+        .flatten()
+        .flatten()
+        .filter_map(|fd| {
+            let target = fs::read_link(fd.path()).ok()?;
+            let inode = target
+                .to_str()?
+                .strip_prefix("socket:[")?
+                .strip_suffix(']')?;
+            Some(inode.to_string())
+        })
+        .collect();
+
+    let mut ports: Vec<u16> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|table| fs::read_to_string(table).ok())
+        .flat_map(|table| {
+            listening(&table)
+                .into_iter()
+                .filter(|(_, inode)| sockets.contains(*inode))
+                .map(|(port, _)| port)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    ports.sort_unstable();
+    ports.dedup();
+    log::trace!("process {pid} listens on {ports:?}");
+    ports
+}
+
+/// the ports and socket inodes of the listening sockets of `/proc/net/tcp` tables,
+/// their header lines are skipped
+fn listening(tables: &str) -> Vec<(u16, &str)> {
+    tables
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // 0A is LISTEN
+            if fields.get(3) != Some(&"0A") {
+                return None;
+            }
+            let (_, port) = fields.get(1)?.rsplit_once(':')?;
+            Some((u16::from_str_radix(port, 16).ok()?, *fields.get(9)?))
+        })
+        .collect()
+}
+
+/// whether something accepts connections on `port` of localhost, or of the container `name`
+async fn accepts(container: Option<&str>, port: u16) -> bool {
+    match container {
+        // docker accepts the connections to published ports itself, even before the program listens,
+        // so the sockets of the container are looked at instead
+        Some(name) => crate::command!(
+            "docker",
+            "exec",
+            name,
+            "cat",
+            "/proc/net/tcp",
+            "/proc/net/tcp6"
+        )
+        .output()
+        .await
+        .is_ok_and(|output| {
+            listening(&String::from_utf8_lossy(&output.stdout))
+                .iter()
+                .any(|(p, _)| *p == port)
+        }),
+        None => TcpStream::connect(("127.0.0.1", port)).await.is_ok(),
+    }
+}
+
+/// ports the process `pid` and its children listen on, only known on linux
+#[cfg(not(target_os = "linux"))]
+pub fn listening_ports(_pid: u32) -> Vec<u16> {
+    vec![]
+}
+
+#[cfg(unix)]
+fn terminate(child: &Child) {
+    if let Some(pid) = child.id() {
+        log::debug!("sending SIGTERM to process {pid}");
+        // SAFETY: the process is not reaped yet, so the pid can't have been reused
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate(_child: &Child) {
+    // there is no polite way to ask, it's killed once the grace period is over
+}
+
+/// prints the lines of `pipe` after `prefix`, on stderr if `stderr`,
+/// telling `found` when one contains `pattern`
+async fn forward(
+    pipe: impl AsyncRead + Unpin,
+    stderr: bool,
+    prefix: String,
+    pattern: Option<String>,
+    found: watch::Sender<bool>,
+) {
+    let mut lines = BufReader::new(pipe).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if stderr {
+            eprintln!("{prefix}{line}");
+        } else {
+            println!("{prefix}{line}");
+        }
+
+        if pattern.as_deref().is_some_and(|p| line.contains(p)) {
+            found.send_replace(true);
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use crate::file_type::FileType;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+/// A compiler message pointing (when possible) to a location in the source
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// compiler specific code, e.g. `E0425` or `TS2322`
+    pub code: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl Diagnostic {
+    pub fn error(message: &str) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.to_string(),
+            code: None,
+            file: None,
+            line: None,
+            column: None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// parses the build output of the compiler used for `file_type`
+    pub fn parse(file_type: &FileType, output: &str) -> Vec<Self> {
+        match file_type {
+            FileType::Rust => parse_rustc_json(output),
+            FileType::Go => parse_go(output),
+            FileType::Typescript => parse_tsc(output),
+            FileType::Java => parse_javac(output),
+            _ => parse_gcc(output),
+        }
+    }
+
+    /// the diagnostics of a build, the whole `output` becomes one error
+    /// when the build failed without an error the parser knows, e.g. one of the linker
+    pub fn from_build(file_type: &FileType, output: &str, success: bool) -> Vec<Self> {
+        let mut diagnostics = Self::parse(file_type, output);
+
+        if !success && !diagnostics.iter().any(|d| d.is_error()) {
+            diagnostics.push(Self::error(output.trim()));
+        }
+
+        diagnostics
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "{}", "error".red().bold()),
+            Self::Warning => write!(f, "{}", "warning".yellow().bold()),
+            Self::Note => write!(f, "{}", "note".blue().bold()),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.severity)?;
+
+        if let Some(code) = &self.code {
+            write!(f, "[{code}]")?;
+        }
+
+        if let Some(file) = &self.file {
+            let mut location = file.clone();
+
+            if let Some(line) = self.line {
+                location.push_str(&format!(":{line}"));
+            }
+
+            if let Some(column) = self.column {
+                location.push_str(&format!(":{column}"));
+            }
+
+            write!(f, " {}", location.dimmed())?;
+        }
+
+        write!(f, " {}", self.message)
+    }
+}
+
+/// prints the diagnostics as a compact list, errors first,
+/// each followed by the line of source it points to
+pub fn render(diagnostics: &[Diagnostic]) {
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warnings = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Warning)
+        .count();
+
+    if errors > 0 {
+        eprintln!(
+            "🔨 Build failed: {} error(s), {} warning(s)",
+            errors.to_string().red(),
+            warnings.to_string().yellow()
+        );
+    } else if warnings > 0 {
+        eprintln!("🔨 {} warning(s)", warnings.to_string().yellow());
+    }
+
+    let mut sorted: Vec<&Diagnostic> = diagnostics.iter().collect();
+    sorted.sort_by_key(|d| !d.is_error());
+
+    for diagnostic in sorted {
+        eprintln!("   {}", diagnostic);
+
+        if let Some(snippet) = snippet(diagnostic) {
+            eprintln!("{}", snippet);
+        }
+    }
+}
+
+fn snippet(diagnostic: &Diagnostic) -> Option<String> {
+    let file = diagnostic.file.as_ref()?;
+    let line = diagnostic.line?;
+
+    let source = std::fs::read_to_string(file).ok()?;
+    let text = source.lines().nth(line.checked_sub(1)?)?;
+
+    let gutter = format!("{line:>6} | ");
+    let mut snippet = format!("{}{}", gutter.dimmed(), text);
+
+    if let Some(column) = diagnostic.column {
+        let padding = " ".repeat(gutter.len() - 2);
+        let offset = " ".repeat(column.saturating_sub(1));
+        snippet.push_str(&format!(
+            "\n{}{}{}",
+            padding.dimmed(),
+            "| ".dimmed(),
+            offset
+        ));
+        snippet.push_str(&"^".red().to_string());
+    }
+
+    Some(snippet)
+}
+
+/// splits `file:line:col: rest` or `file:line: rest`
+fn split_location(line: &str) -> Option<(String, usize, Option<usize>, &str)> {
+    let mut parts = line.splitn(4, ':');
+
+    let file = parts.next()?.trim();
+    let line_no = parts.next()?.trim().parse().ok()?;
+    let third = parts.next()?;
+
+    if file.is_empty() || file.contains(' ') {
+        return None;
+    }
+
+    match third.trim().parse() {
+        Ok(column) => Some((
+            file.to_string(),
+            line_no,
+            Some(column),
+            parts.next()?.trim(),
+        )),
+        Err(_) => {
+            let rest = line.splitn(3, ':').nth(2)?.trim();
+            Some((file.to_string(), line_no, None, rest))
+        }
+    }
+}
+
+/// splits `error: message` into its severity and message
+fn split_severity(rest: &str) -> Option<(Severity, &str)> {
+    [
+        ("fatal error:", Severity::Error),
+        ("error:", Severity::Error),
+        ("warning:", Severity::Warning),
+        ("note:", Severity::Note),
+    ]
+    .into_iter()
+    .find_map(|(prefix, severity)| Some((severity, rest.strip_prefix(prefix)?.trim())))
+}
+
+/// gcc and clang: `main.c:4:5: error: expected ';' before '}' token`
+fn parse_gcc(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let (file, line, column, rest) = split_location(l)?;
+            let (severity, message) = split_severity(rest)?;
+
+            Some(Diagnostic {
+                severity,
+                message: message.to_string(),
+                code: None,
+                file: Some(file),
+                line: Some(line),
+                column,
+            })
+        })
+        .collect()
+}
+
+/// go: `./main.go:5:2: undefined: x`, every message is an error
+fn parse_go(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let (file, line, column, rest) = split_location(l)?;
+
+            Some(Diagnostic {
+                severity: Severity::Error,
+                message: rest.to_string(),
+                code: None,
+                file: Some(file),
+                line: Some(line),
+                column,
+            })
+        })
+        .collect()
+}
+
+/// tsc: `main.ts(3,5): error TS2322: message` or `main.ts:3:5 - error TS2322: message`
+fn parse_tsc(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let (location, rest) = l
+                .split_once("): ")
+                .map(|(loc, rest)| (loc.replacen('(', ":", 1).replace(',', ":"), rest))
+                .or_else(|| {
+                    l.split_once(" - ")
+                        .map(|(loc, rest)| (loc.to_string(), rest))
+                })?;
+
+            let mut location = location.rsplitn(3, ':');
+            let column = location.next()?.trim().parse().ok()?;
+            let line = location.next()?.trim().parse().ok()?;
+            let file = location.next()?.to_string();
+
+            let (kind, rest) = rest.split_once(' ')?;
+            let severity = match kind {
+                "error" => Severity::Error,
+                "warning" => Severity::Warning,
+                _ => return None,
+            };
+
+            let (code, message) = rest.split_once(": ").unwrap_or(("", rest));
+
+            Some(Diagnostic {
+                severity,
+                message: message.to_string(),
+                code: (!code.is_empty()).then(|| code.to_string()),
+                file: Some(file),
+                line: Some(line),
+                column: Some(column),
+            })
+        })
+        .collect()
+}
+
+/// javac: `Main.java:5: error: cannot find symbol`
+/// followed by the source line, a caret line and optional `symbol:` details
+fn parse_javac(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+
+    for l in output.lines() {
+        if let Some((file, line, _, rest)) = split_location(l) {
+            let Some((severity, message)) = split_severity(rest) else {
+                continue;
+            };
+
+            diagnostics.push(Diagnostic {
+                severity,
+                message: message.to_string(),
+                code: None,
+                file: Some(file),
+                line: Some(line),
+                column: None,
+            });
+        } else if let Some(last) = diagnostics.last_mut() {
+            // the caret line gives us the column
+            if last.column.is_none() && l.trim() == "^" {
+                last.column = l.find('^').map(|c| c + 1);
+            } else if let Some(detail) = l.trim().strip_prefix("symbol:") {
+                last.message.push_str(&format!(" ({})", detail.trim()));
+            }
+        }
+    }
+
+    diagnostics
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<RustcMessage>,
+}
+
+#[derive(Deserialize)]
+struct RustcMessage {
+    message: String,
+    level: String,
+    code: Option<RustcCode>,
+    spans: Vec<RustcSpan>,
+}
+
+#[derive(Deserialize)]
+struct RustcCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct RustcSpan {
+    file_name: String,
+    line_start: usize,
+    column_start: usize,
+    is_primary: bool,
+}
+
+/// cargo `--message-format json`, one JSON object per line
+fn parse_rustc_json(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|l| serde_json::from_str::<CargoMessage>(l).ok())
+        .filter(|m| m.reason == "compiler-message")
+        .filter_map(|m| {
+            let message = m.message?;
+
+            let severity = match message.level.as_str() {
+                "error" | "error: internal compiler error" => Severity::Error,
+                "warning" => Severity::Warning,
+                _ => Severity::Note,
+            };
+
+            let span = message.spans.iter().find(|s| s.is_primary);
+
+            // `aborting due to previous error`, `N warnings emitted`
+            // and `rustc --explain` summaries
+            if span.is_none()
+                && (severity != Severity::Error || message.message.starts_with("aborting due to"))
+            {
+                return None;
+            }
+
+            Some(Diagnostic {
+                severity,
+                message: message.message,
+                code: message.code.map(|c| c.code),
+                file: span.map(|s| s.file_name.clone()),
+                line: span.map(|s| s.line_start),
+                column: span.map(|s| s.column_start),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// severity, file, line, column and message, to compare in one go
+    fn summary(d: &Diagnostic) -> (Severity, Option<&str>, Option<usize>, Option<usize>, &str) {
+        (
+            d.severity,
+            d.file.as_deref(),
+            d.line,
+            d.column,
+            d.message.as_str(),
+        )
+    }
+
+    #[test]
+    fn rustc_json_keeps_located_messages_with_their_codes() {
+        let output = [
+            r#"{"reason":"compiler-artifact","package_id":"path+file:///tmp/rs#0.1.0","fresh":true}"#,
+            r#"{"reason":"compiler-message","package_id":"path+file:///tmp/rs#0.1.0","message":{"rendered":"warning: unused variable: `unused`\n","$message_type":"diagnostic","children":[],"level":"warning","message":"unused variable: `unused`","spans":[{"byte_end":24,"byte_start":18,"column_end":15,"column_start":9,"expansion":null,"file_name":"src/main.rs","is_primary":true,"label":null,"line_end":2,"line_start":2,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}],"code":{"code":"unused_variables","explanation":null}}}"#,
+            r#"{"reason":"compiler-message","package_id":"path+file:///tmp/rs#0.1.0","message":{"rendered":"error[E0425]: cannot find value `x` in this scope\n","$message_type":"diagnostic","children":[],"level":"error","message":"cannot find value `x` in this scope","spans":[{"byte_end":52,"byte_start":51,"column_end":21,"column_start":20,"expansion":null,"file_name":"src/main.rs","is_primary":true,"label":"not found in this scope","line_end":3,"line_start":3,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}],"code":{"code":"E0425","explanation":"An unresolved name was used."}}}"#,
+            r#"{"reason":"compiler-message","package_id":"path+file:///tmp/rs#0.1.0","message":{"rendered":"error: aborting due to 1 previous error; 1 warning emitted\n","$message_type":"diagnostic","children":[],"level":"error","message":"aborting due to 1 previous error; 1 warning emitted","spans":[],"code":null}}"#,
+            r#"{"reason":"compiler-message","package_id":"path+file:///tmp/rs#0.1.0","message":{"rendered":"For more information about this error, try `rustc --explain E0425`.\n","$message_type":"diagnostic","children":[],"level":"failure-note","message":"For more information about this error, try `rustc --explain E0425`.","spans":[],"code":null}}"#,
+            r#"{"reason":"build-finished","success":false}"#,
+        ]
+        .join("\n");
+
+        let diagnostics = Diagnostic::from_build(&FileType::Rust, &output, false);
+        let summaries: Vec<_> = diagnostics.iter().map(summary).collect();
+        assert_eq!(
+            summaries,
+            [
+                (
+                    Severity::Warning,
+                    Some("src/main.rs"),
+                    Some(2),
+                    Some(9),
+                    "unused variable: `unused`"
+                ),
+                (
+                    Severity::Error,
+                    Some("src/main.rs"),
+                    Some(3),
+                    Some(20),
+                    "cannot find value `x` in this scope"
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[0].code.as_deref(), Some("unused_variables"));
+        assert_eq!(diagnostics[1].code.as_deref(), Some("E0425"));
+    }
+
+    #[test]
+    fn gcc_skips_the_context_and_source_lines() {
+        let output = "\
+main.c: In function 'main':
+main.c:2:9: warning: unused variable 'x' [-Wunused-variable]
+    2 |     int x = 1
+      |         ^
+main.c:3:5: error: expected ',' or ';' before 'return'
+    3 |     return y;
+      |     ^~~~~~
+";
+
+        let diagnostics = Diagnostic::from_build(&FileType::C, output, false);
+        let summaries: Vec<_> = diagnostics.iter().map(summary).collect();
+        assert_eq!(
+            summaries,
+            [
+                (
+                    Severity::Warning,
+                    Some("main.c"),
+                    Some(2),
+                    Some(9),
+                    "unused variable 'x' [-Wunused-variable]"
+                ),
+                (
+                    Severity::Error,
+                    Some("main.c"),
+                    Some(3),
+                    Some(5),
+                    "expected ',' or ';' before 'return'"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn go_reports_every_located_line_as_an_error() {
+        let output = "\
+# command-line-arguments
+./main.go:5:2: undefined: x
+./main.go:6:2: declared and not used: y
+";
+
+        let diagnostics = Diagnostic::from_build(&FileType::Go, output, false);
+        let summaries: Vec<_> = diagnostics.iter().map(summary).collect();
+        assert_eq!(
+            summaries,
+            [
+                (
+                    Severity::Error,
+                    Some("./main.go"),
+                    Some(5),
+                    Some(2),
+                    "undefined: x"
+                ),
+                (
+                    Severity::Error,
+                    Some("./main.go"),
+                    Some(6),
+                    Some(2),
+                    "declared and not used: y"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn tsc_reads_both_location_styles() {
+        let output = "\
+main.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.
+main.ts:8:1 - error TS2304: Cannot find name 'y'.
+
+8 y;
+  ~
+
+Found 2 errors in the same file, starting at: main.ts:3
+";
+
+        let diagnostics = Diagnostic::from_build(&FileType::Typescript, output, false);
+        let summaries: Vec<_> = diagnostics.iter().map(summary).collect();
+        assert_eq!(
+            summaries,
+            [
+                (
+                    Severity::Error,
+                    Some("main.ts"),
+                    Some(3),
+                    Some(7),
+                    "Type 'string' is not assignable to type 'number'."
+                ),
+                (
+                    Severity::Error,
+                    Some("main.ts"),
+                    Some(8),
+                    Some(1),
+                    "Cannot find name 'y'."
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[0].code.as_deref(), Some("TS2322"));
+        assert_eq!(diagnostics[1].code.as_deref(), Some("TS2304"));
+    }
+
+    #[test]
+    fn javac_takes_the_column_from_the_caret_and_adds_the_symbol() {
+        let output = "\
+Main.java:3: error: cannot find symbol
+        System.out.println(y);
+                           ^
+  symbol:   variable y
+  location: class Main
+1 error
+";
+
+        let diagnostics = Diagnostic::from_build(&FileType::Java, output, false);
+        let summaries: Vec<_> = diagnostics.iter().map(summary).collect();
+        assert_eq!(
+            summaries,
+            [(
+                Severity::Error,
+                Some("Main.java"),
+                Some(3),
+                Some(28),
+                "cannot find symbol (variable y)"
+            )]
+        );
+    }
+
+    #[test]
+    fn a_failed_build_without_a_known_error_becomes_one_error() {
+        let output = "\
+link.c: In function 'main':
+link.c:3:9: warning: unused variable 'unused' [-Wunused-variable]
+    3 |     int unused;
+      |         ^~~~~~
+/usr/bin/ld: /tmp/cca0F4Nn.o: in function `main':
+link.c:(.text+0x5): undefined reference to `foo'
+collect2: error: ld returned 1 exit status
+";
+
+        let diagnostics = Diagnostic::from_build(&FileType::C, output, false);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(
+            summary(&diagnostics[1]),
+            (Severity::Error, None, None, None, output.trim())
+        );
+    }
+
+    #[test]
+    fn a_successful_build_keeps_only_what_was_parsed() {
+        let output = "main.c:2:9: warning: unused variable 'x' [-Wunused-variable]\n";
+        let diagnostics = Diagnostic::from_build(&FileType::C, output, true);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(Diagnostic::from_build(&FileType::C, "", true).is_empty());
+    }
+}
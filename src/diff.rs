@@ -0,0 +1,178 @@
+use crate::utils::clear_screen;
+use colored::*;
+
+/// A line of a line by line diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl Line<'_> {
+    pub fn is_change(&self) -> bool {
+        !matches!(self, Self::Same(_))
+    }
+}
+
+/// above this many cells the middle of the diff is not aligned, only replaced as a whole
+const MAX_TABLE: usize = 4_000_000;
+
+/// the lines of `old` and `new` aligned on their longest common subsequence
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut diff: Vec<Line> = old[..prefix].iter().map(|l| Line::Same(l)).collect();
+
+    if a.len() * b.len() > MAX_TABLE {
+        diff.extend(a.iter().map(|l| Line::Removed(l)));
+        diff.extend(b.iter().map(|l| Line::Added(l)));
+    } else {
+        // lengths of the common subsequences of a[i..] and b[j..]
+        let width = b.len() + 1;
+        let mut table = vec![0usize; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                table[i * width + j] = if a[i] == b[j] {
+                    table[(i + 1) * width + j + 1] + 1
+                } else {
+                    table[(i + 1) * width + j].max(table[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i] == b[j] {
+                diff.push(Line::Same(a[i]));
+                i += 1;
+                j += 1;
+            } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+                diff.push(Line::Removed(a[i]));
+                i += 1;
+            } else {
+                diff.push(Line::Added(b[j]));
+                j += 1;
+            }
+        }
+
+        diff.extend(a[i..].iter().map(|l| Line::Removed(l)));
+        diff.extend(b[j..].iter().map(|l| Line::Added(l)));
+    }
+
+    diff.extend(old[old.len() - suffix..].iter().map(|l| Line::Same(l)));
+    diff
+}
+
+/// prints the changes between `old` and `new` as coloured hunks with `context` lines around them,
+/// returns whether there were any
+pub fn render(old: &str, new: &str, context: usize) -> bool {
+    let diff = lines(old, new);
+
+    let mut visible = vec![false; diff.len()];
+    for (i, line) in diff.iter().enumerate() {
+        if line.is_change() {
+            let start = i.saturating_sub(context);
+            let end = (i + context + 1).min(diff.len());
+            visible[start..end].iter_mut().for_each(|v| *v = true);
+        }
+    }
+
+    // line numbers in `old` and `new` before each line of the diff
+    let mut numbers = Vec::with_capacity(diff.len());
+    let (mut old_line, mut new_line) = (1, 1);
+    for line in &diff {
+        numbers.push((old_line, new_line));
+        match line {
+            Line::Same(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Line::Removed(_) => old_line += 1,
+            Line::Added(_) => new_line += 1,
+        }
+    }
+
+    let mut i = 0;
+    while i < diff.len() {
+        if !visible[i] {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < diff.len() && visible[i] {
+            i += 1;
+        }
+
+        let hunk = &diff[start..i];
+        let (old_start, new_start) = numbers[start];
+        let old_count = hunk.iter().filter(|l| !matches!(l, Line::Added(_))).count();
+        let new_count = hunk
+            .iter()
+            .filter(|l| !matches!(l, Line::Removed(_)))
+            .count();
+
+        println!(
+            "{}",
+            format!("@@ -{old_start},{old_count} +{new_start},{new_count} @@").cyan()
+        );
+
+        for line in hunk {
+            match line {
+                Line::Same(l) => println!(" {l}"),
+                Line::Removed(l) => println!("{}", format!("-{l}").red()),
+                Line::Added(l) => println!("{}", format!("+{l}").green()),
+            }
+        }
+    }
+
+    visible.contains(&true)
+}
+
+/// How `--output-diff` shows the output of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputDiff {
+    /// replace the output with its diff against the previous run's once the run ends
+    Compact,
+    /// keep the full output and print the diff below it
+    Full,
+}
+
+/// prints how `output` changed since the `previous` run, the output was already streamed
+pub fn render_output(previous: Option<&str>, output: &str, mode: OutputDiff) {
+    if mode == OutputDiff::Compact {
+        clear_screen();
+        eprintln!("🏃File Changed!");
+        eprintln!();
+    }
+
+    let Some(previous) = previous else {
+        // nothing to compare the first run to
+        if mode == OutputDiff::Compact {
+            print!("{output}");
+        }
+        return;
+    };
+
+    if mode == OutputDiff::Full {
+        eprintln!();
+        eprintln!("{}", "🔀 Output diff:".bold());
+    }
+
+    if !render(previous, output, 1) {
+        eprintln!("{}", "🟰 Same output as the previous run".dimmed());
+    }
+}
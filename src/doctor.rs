@@ -0,0 +1,170 @@
+use clap::ValueEnum;
+use colored::*;
+use runner::{
+    command::docker,
+    file_type::FileType,
+    toolchain::{Toolchain, Version},
+    utils::format_table,
+};
+
+struct Row {
+    runtime: String,
+    binary: String,
+    version: String,
+    path: String,
+    image: String,
+    pulled: String,
+    default: String,
+    local: String,
+}
+
+/// prints, for every runtime, the local toolchain, the docker image and what runner would use
+pub async fn run() -> anyhow::Result<()> {
+    let daemon = docker::is_daemon_running().await;
+
+    if !daemon {
+        eprintln!("🐳 Docker is not reachable, use --no-docker to run locally");
+        eprintln!();
+    }
+
+    let mut rows = vec![];
+
+    for file_type in FileType::value_variants() {
+        if matches!(file_type, FileType::Unsupported) {
+            continue;
+        }
+
+        rows.push(row(file_type, daemon).await);
+    }
+
+    print_table(&rows);
+
+    Ok(())
+}
+
+async fn row(file_type: &FileType, daemon: bool) -> Row {
+    let toolchain = file_type.toolchain().await;
+    let min_version = file_type.get_min_version();
+
+    // a binary that's installed but too old is worth reporting
+    let mut outdated: Option<Toolchain> = None;
+    if toolchain.is_none() {
+        for binary in file_type.get_binaries() {
+            if let Some(found) = Toolchain::discover(&binary).await {
+                outdated = Some(found);
+                break;
+            }
+        }
+    }
+
+    let (binary, version, path, local) = match (&toolchain, &outdated) {
+        (Some(t), _) => (
+            t.binary.clone(),
+            format_version(t.version),
+            t.path.display().to_string(),
+            t.path.display().to_string(),
+        ),
+        (None, Some(t)) => (
+            t.binary.clone(),
+            format!(
+                "{} (< {})",
+                format_version(t.version),
+                min_version.unwrap_or_default()
+            ),
+            t.path.display().to_string(),
+            "unavailable".to_string(),
+        ),
+        (None, None) => (
+            file_type
+                .get_binaries()
+                .first()
+                .cloned()
+                .unwrap_or_default(),
+            "-".to_string(),
+            "not found".to_string(),
+            "unavailable".to_string(),
+        ),
+    };
+
+    let image = file_type.resolve_docker_image().await;
+
+    let pulled = match &image {
+        Some(image) if daemon => docker::image_exists_on_machine(&image.to_string())
+            .await
+            .unwrap_or(false),
+        _ => false,
+    };
+
+    let default = match &image {
+        Some(image) if !daemon => format!("docker {image} (unreachable)"),
+        Some(image) if pulled => format!("docker {image}"),
+        Some(image) => format!("docker {image} (pull)"),
+        None => local.clone(),
+    };
+
+    let pulled = match (&image, daemon) {
+        (None, _) | (_, false) => "-",
+        _ if pulled => "yes",
+        _ => "no",
+    };
+
+    Row {
+        runtime: format!("{file_type:?}"),
+        binary,
+        version,
+        path,
+        image: image.map(|i| i.to_string()).unwrap_or("-".to_string()),
+        pulled: pulled.to_string(),
+        default,
+        local,
+    }
+}
+
+fn format_version(version: Option<Version>) -> String {
+    version.map(|v| v.to_string()).unwrap_or("?".to_string())
+}
+
+fn print_table(rows: &[Row]) {
+    let header = Row {
+        runtime: "RUNTIME".into(),
+        binary: "BINARY".into(),
+        version: "VERSION".into(),
+        path: "PATH".into(),
+        image: "IMAGE".into(),
+        pulled: "PULLED".into(),
+        default: "RUNS WITH".into(),
+        local: "WITH --no-docker".into(),
+    };
+
+    let cells = |r: &Row| {
+        vec![
+            r.runtime.clone(),
+            r.binary.clone(),
+            r.version.clone(),
+            r.path.clone(),
+            r.image.clone(),
+            r.pulled.clone(),
+            r.default.clone(),
+            r.local.clone(),
+        ]
+    };
+
+    let lines = format_table(
+        &std::iter::once(&header)
+            .chain(rows)
+            .map(cells)
+            .collect::<Vec<_>>(),
+    );
+
+    println!("{}", lines[0].bold());
+
+    for (row, text) in rows.iter().zip(&lines[1..]) {
+        if row.local == "unavailable" && row.default == row.local {
+            println!("{}", text.red());
+        } else if row.local == "unavailable" {
+            println!("{}", text.yellow());
+        } else {
+            println!("{}", text);
+        }
+    }
+}
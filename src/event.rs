@@ -0,0 +1,170 @@
+use crate::{
+    core::{RunOutcome, RunStatus},
+    diagnostic::Diagnostic,
+};
+use serde::Serialize;
+use std::{path::PathBuf, time::Duration};
+use tokio::sync::broadcast;
+
+/// bumped whenever an event changes in a way that can break its readers,
+/// adding events or fields doesn't change it
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// What happens while a file is watched and run,
+/// `--format json` reports them one json object per line on stdout
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    WatchStarted {
+        path: PathBuf,
+        runtime: String,
+    },
+    ChangeDetected {
+        path: PathBuf,
+    },
+    BuildStarted,
+    BuildFinished {
+        success: bool,
+        duration_ms: f64,
+        diagnostics: Vec<Diagnostic>,
+    },
+    RunFinished {
+        /// `null` when the build failed or the program was killed by a signal
+        exit_code: Option<i32>,
+        /// `success`, `build-failed`, `failed` or `timed-out`
+        status: &'static str,
+        build_ms: f64,
+        run_ms: f64,
+        /// stdout followed by stderr
+        output: Option<String>,
+    },
+    ImageUsed {
+        image: String,
+    },
+}
+
+impl Event {
+    pub fn run_finished(outcome: &RunOutcome) -> Self {
+        let (exit_code, status) = match outcome.status {
+            RunStatus::Success => (Some(0), "success"),
+            RunStatus::BuildFailed => (None, "build-failed"),
+            RunStatus::Failed(code) => (code, "failed"),
+            RunStatus::Killed(_) => (None, "failed"),
+            RunStatus::TimedOut => (None, "timed-out"),
+        };
+
+        Self::RunFinished {
+            exit_code,
+            status,
+            build_ms: millis(outcome.build_duration),
+            run_ms: millis(outcome.run_duration),
+            output: outcome.output.clone(),
+        }
+    }
+
+    pub fn build_finished(success: bool, duration: Duration, diagnostics: &[Diagnostic]) -> Self {
+        Self::BuildFinished {
+            success,
+            duration_ms: millis(duration),
+            diagnostics: diagnostics.to_vec(),
+        }
+    }
+
+    /// the event as a line of json, tagged with the schema version
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Line<'a> {
+            version: u32,
+            #[serde(flatten)]
+            event: &'a Event,
+        }
+
+        serde_json::to_string(&Line {
+            version: SCHEMA_VERSION,
+            event: self,
+        })
+        .expect("events always serialize")
+    }
+}
+
+/// Where events go: stdout with `--format json`, and whoever subscribed to them
+#[derive(Debug, Clone)]
+pub struct Events {
+    json: bool,
+    subscribers: broadcast::Sender<Event>,
+}
+
+impl Events {
+    pub fn new(json: bool) -> Self {
+        let (subscribers, _) = broadcast::channel(64);
+        Self { json, subscribers }
+    }
+
+    /// the channel subscribers receive the events from
+    pub fn subscribers(&self) -> broadcast::Sender<Event> {
+        self.subscribers.clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.subscribers.subscribe()
+    }
+
+    pub fn emit(&self, event: Event) {
+        if self.json {
+            println!("{}", event.to_json());
+        }
+
+        // nobody may be subscribed
+        let _ = self.subscribers.send(event);
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runner;
+
+    #[tokio::test]
+    async fn a_run_emits_build_started_build_finished_then_run_finished() {
+        let path = std::env::temp_dir().join(format!("runner-events-{}.sh", std::process::id()));
+        std::fs::write(&path, "echo hello\n").unwrap();
+
+        let runner = Runner::new(&path).docker(false).capture();
+        let mut events = runner.subscribe();
+        let outcome = runner.run().await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(outcome.unwrap().status, RunStatus::Success);
+
+        let mut received = vec![];
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+
+        assert!(matches!(
+            received.as_slice(),
+            [
+                Event::BuildStarted,
+                Event::BuildFinished { success: true, .. },
+                Event::RunFinished {
+                    exit_code: Some(0),
+                    status: "success",
+                    ..
+                },
+            ]
+        ));
+    }
+
+    #[test]
+    fn json_lines_carry_the_version_and_a_kebab_case_tag() {
+        let json = Event::build_finished(true, Duration::from_millis(5), &[]).to_json();
+        assert!(json.contains(r#""version":1"#));
+        assert!(json.contains(r#""event":"build-finished""#));
+
+        let json = Event::BuildStarted.to_json();
+        assert!(json.contains(r#""event":"build-started""#));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    command::{command_line, docker, quote},
+    core::{docker_setup, prepare, RunOptions},
+    file_type::FileType,
+};
+use colored::*;
+use serde::Serialize;
+use std::{cell::RefCell, fmt, path::Path};
+use tokio::process::Command;
+
+tokio::task_local! {
+    /// command lines the build steps would run, recorded instead of running them while explaining
+    static RECORDED: RefCell<Vec<String>>;
+}
+
+/// whether the current task explains a run rather than running it
+pub fn is_dry_run() -> bool {
+    RECORDED.try_with(|_| ()).is_ok()
+}
+
+/// records `command` when explaining, returns whether it was, in which case it must not run
+pub fn record(command: &Command) -> bool {
+    RECORDED
+        .try_with(|recorded| recorded.borrow_mut().push(command_line(command)))
+        .is_ok()
+}
+
+/// Where the program runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Docker,
+    Local,
+    /// locally, with the command given by `--command`
+    Command,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Docker => write!(f, "docker"),
+            Self::Local => write!(f, "local"),
+            Self::Command => write!(f, "local, custom command"),
+        }
+    }
+}
+
+/// What running a file would do, see `runner explain`
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    pub runtime: String,
+    /// why the file runs with `runtime`
+    pub reason: String,
+    pub backend: Backend,
+    pub image: Option<String>,
+    /// `host:container` volumes of the container
+    pub mounts: Vec<String>,
+    /// `KEY=VALUE` variables set for the program, with the values passed with `-e` masked
+    pub env: Vec<String>,
+    /// every command line that would run, in order
+    pub commands: Vec<String>,
+}
+
+/// what running `path` as `file_type` with `options` would do, without building, running
+/// or writing anything, the detection probes (`--version` and such) still run
+pub async fn explain(
+    file_type: &FileType,
+    reason: String,
+    path: &Path,
+    options: &RunOptions,
+) -> anyhow::Result<Explanation> {
+    let mut explanation = Explanation {
+        runtime: file_type.to_string(),
+        reason,
+        backend: Backend::Local,
+        image: None,
+        mounts: vec![],
+        env: vec![],
+        commands: vec![],
+    };
+
+    if !options.no_docker && file_type.get_docker_image().is_some() {
+        // resolving the setup of rust and go files generates their projects, unless recorded
+        let setup = RECORDED
+            .scope(
+                RefCell::new(vec![]),
+                docker_setup(
+                    file_type,
+                    path,
+                    options.command.clone(),
+                    options.docker_image.clone(),
+                    &options.crates,
+                ),
+            )
+            .await?;
+
+        let (args, image, _) = docker::run_command(
+            file_type,
+            path.to_str().unwrap_or_default(),
+            setup.command,
+            setup.image,
+            &setup.volumes,
+            options,
+        )?;
+
+        explanation.backend = Backend::Docker;
+        explanation.image = Some(image);
+        explanation.mounts = args
+            .iter()
+            .zip(&args[1..])
+            .filter(|(flag, _)| *flag == "-v")
+            .map(|(_, volume)| volume.clone())
+            .collect();
+        // the values passed with `-e` are masked, like for local runs
+        let masked: Vec<String> = args
+            .iter()
+            .zip(std::iter::once(&String::new()).chain(&args))
+            .map(|(arg, flag)| match arg.split_once('=') {
+                Some((key, _)) if flag == "-e" => format!("{key}=****"),
+                _ => arg.clone(),
+            })
+            .collect();
+        explanation.env = masked
+            .iter()
+            .zip(&masked[1..])
+            .filter(|(flag, _)| *flag == "-e")
+            .map(|(_, variable)| variable.clone())
+            .collect();
+        explanation.commands = vec![std::iter::once("docker".to_string())
+            .chain(masked.iter().map(|a| quote(a)))
+            .collect::<Vec<_>>()
+            .join(" ")];
+
+        return Ok(explanation);
+    }
+
+    // docker is ruled out already, `prepare` would only say so again
+    let options = &RunOptions {
+        no_docker: true,
+        ..options.clone()
+    };
+
+    let (prepared, mut commands) = RECORDED
+        .scope(RefCell::new(vec![]), async {
+            let prepared = prepare(file_type, path, options).await;
+            (prepared, RECORDED.with(|recorded| recorded.take()))
+        })
+        .await;
+
+    if let Some(command) = prepared?.command() {
+        let masked: Vec<&str> = options
+            .env
+            .iter()
+            .map(|e| e.split_once('=').map_or(e.as_str(), |(key, _)| key))
+            .collect();
+
+        explanation.env = command
+            .as_std()
+            .get_envs()
+            .filter_map(|(key, value)| {
+                let key = key.to_string_lossy();
+                let value = if masked.contains(&key.as_ref()) {
+                    "****".into()
+                } else {
+                    value?.to_string_lossy()
+                };
+                Some(format!("{key}={value}"))
+            })
+            .collect();
+
+        commands.push(command_line(command));
+    }
+
+    if options.command.is_some() {
+        explanation.backend = Backend::Command;
+    }
+
+    explanation.commands = commands;
+    Ok(explanation)
+}
+
+/// why `path` runs with the runtime of its extension
+pub fn detection_reason(path: &Path) -> String {
+    match path.extension() {
+        Some(ext) => format!("detected from the .{} extension", ext.to_string_lossy()),
+        None => "the file has no extension".to_string(),
+    }
+}
+
+impl Explanation {
+    pub fn render(&self) {
+        let none = || "-".dimmed().to_string();
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                none()
+            } else {
+                items.join(" ")
+            }
+        };
+
+        println!(
+            "{} {} {}",
+            "runtime:".bold(),
+            self.runtime.yellow(),
+            format!("({})", self.reason).dimmed()
+        );
+        println!("{} {}", "backend:".bold(), self.backend);
+        println!(
+            "{} {}",
+            "image:  ".bold(),
+            self.image.clone().unwrap_or_else(none)
+        );
+        println!("{} {}", "mounts: ".bold(), list(&self.mounts));
+        println!("{} {}", "env:    ".bold(), list(&self.env));
+        println!("{}", "commands:".bold());
+        for command in &self.commands {
+            println!("  $ {command}");
+        }
+    }
+}
@@ -1,7 +1,12 @@
-use crate::{command::docker::DockerImage, templates};
+use crate::{
+    command::docker::DockerImage,
+    pin::Tool,
+    runtime::{rust::DOCKER_PROJECT_DIR, typescript::TsRuntime},
+    templates,
+    toolchain::{Toolchain, Version},
+};
 use anyhow::anyhow;
-use std::{env, path::PathBuf};
-use tokio::process::Command;
+use std::{env, fmt, path::PathBuf};
 
 #[derive(Debug, clap::ValueEnum, Clone)]
 pub enum FileType {
@@ -34,26 +39,39 @@ impl FileType {
             Self::Perl => Some(DockerImage::latest("perl")),
             Self::Java => Some(DockerImage::alpine("openjdk")),
             Self::Rust => Some(DockerImage::alpine("rust")),
+            Self::Typescript => Some(TsRuntime::Bun.docker_image()),
             _ => None,
         }
     }
 
+    /// like `get_docker_image`, matching the typescript image to the runtime installed locally
+    pub async fn resolve_docker_image(&self) -> Option<DockerImage> {
+        match self {
+            Self::Typescript => Some(
+                TsRuntime::detect()
+                    .await
+                    .unwrap_or(TsRuntime::Bun)
+                    .docker_image(),
+            ),
+            _ => self.get_docker_image(),
+        }
+    }
+
     pub fn get_docker_command(&self) -> Option<String> {
         let entrypoint = self.get_docker_entrypoint();
 
         if let Some(entrypoint) = entrypoint {
             return match self {
-                Self::Java => Some(format!(
-                    "javac /root/app/{entrypoint} && java -cp /root/app Main"
-                )),
+                Self::Java => Some(format!("java /root/app/{entrypoint}")),
                 Self::Node => Some(format!("node /root/app/{entrypoint}")),
-                Self::Python | Self::Python3 => Some(format!("python3 /root/app/{entrypoint}")),
+                Self::Python | Self::Python3 => {
+                    Some(format!("python3 -u /root/app/{entrypoint}"))
+                }
                 Self::Go => Some(format!("go run /root/app/{entrypoint}")),
-                Self::Typescript => Some(format!(
-                    "tsc /root/app/{entrypoint} && node /root/app/main.js"
-                )),
+                Self::Typescript => Some(format!("bun run /root/app/{entrypoint}")),
+                // `--` keeps the program's arguments away from cargo
                 Self::Rust => Some(format!(
-                    "rustc /root/app/{entrypoint} -o /root/app/main && /root/app/main"
+                    "cargo run --quiet --manifest-path {DOCKER_PROJECT_DIR}/Cargo.toml --target-dir {DOCKER_PROJECT_DIR}/target-docker --"
                 )),
                 Self::Shell => Some(format!("bash /root/app/{entrypoint}")),
                 Self::Ruby => Some(format!("ruby /root/app/{entrypoint}")),
@@ -133,38 +151,45 @@ impl TryFrom<&PathBuf> for FileType {
             .and_then(|f| f.to_str())
             .ok_or(anyhow!("cannot extract file extension"))?;
 
-        match ext {
-            "py" => Ok(Self::Python3),
-            "sh" => Ok(Self::Shell),
-            "js" | "mjs" => Ok(Self::Node),
-            "go" => Ok(Self::Go),
-            "ts" => Ok(Self::Typescript),
-            "rs" => Ok(Self::Rust),
-            "pl" => Ok(Self::Perl),
-            "php" => Ok(Self::Php),
-            "rb" => Ok(Self::Ruby),
-            "c" => Ok(Self::C),
-            "cpp" => Ok(Self::Cpp),
-            "java" => Ok(Self::Java),
-            "swift" => Ok(Self::Swift),
-            "scala" => Ok(Self::Scala),
-            "cs" => Ok(Self::CSharp),
-            _ => Ok(Self::Unsupported),
-        }
+        let file_type = match ext {
+            "py" => Self::Python3,
+            "sh" => Self::Shell,
+            "js" | "mjs" => Self::Node,
+            "go" => Self::Go,
+            "ts" => Self::Typescript,
+            "rs" => Self::Rust,
+            "pl" => Self::Perl,
+            "php" => Self::Php,
+            "rb" => Self::Ruby,
+            "c" => Self::C,
+            "cpp" => Self::Cpp,
+            "java" => Self::Java,
+            "swift" => Self::Swift,
+            "scala" => Self::Scala,
+            "cs" => Self::CSharp,
+            _ => Self::Unsupported,
+        };
+
+        log::debug!(
+            "{} is {file_type}, detected from its .{ext} extension",
+            value.display()
+        );
+        Ok(file_type)
     }
 }
 
-impl ToString for FileType {
-    fn to_string(&self) -> String {
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if matches!(self, Self::Shell) {
             if cfg!(windows) {
-                return "bash".to_string();
+                return write!(f, "bash");
             } else {
-                return env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+                let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+                return write!(f, "{shell}");
             }
         }
 
-        match self {
+        let name = match self {
             Self::Python => "python",
             Self::Python3 => "python3",
             Self::Node => "node",
@@ -181,48 +206,87 @@ impl ToString for FileType {
             Self::Scala => "scalac",
             Self::CSharp => "csc",
             _ => "Unsupported",
-        }
-        .to_string()
+        };
+
+        write!(f, "{name}")
     }
 }
 
 impl FileType {
+    /// binaries able to run this file type, in order of preference
+    pub fn get_binaries(&self) -> Vec<String> {
+        match self {
+            // if python3 is not available, we can use python
+            Self::Python3 => vec![Self::Python3.to_string(), Self::Python.to_string()],
+            Self::Python => vec![Self::Python.to_string(), Self::Python3.to_string()],
+            Self::Typescript => TsRuntime::ALL
+                .iter()
+                .map(|r| r.binary().to_string())
+                .collect(),
+            // rust files are built as cargo projects
+            Self::Rust => vec!["cargo".to_string()],
+            Self::Unsupported => vec![],
+            _ => vec![self.to_string()],
+        }
+    }
+
+    /// oldest toolchain runner knows how to drive
+    pub fn get_min_version(&self) -> Option<Version> {
+        match self {
+            // `python` may still be python 2
+            Self::Python3 => Some(Version::new(3, 0, 0)),
+            // module mode by default
+            Self::Go => Some(Version::new(1, 16, 0)),
+            // edition 2021
+            Self::Rust => Some(Version::new(1, 56, 0)),
+            _ => None,
+        }
+    }
+
+    /// the tool whose pinned version applies, typescript depends on the runtime (see `TsRuntime::pin_tool`)
+    pub fn pin_tool(&self) -> Option<Tool> {
+        match self {
+            Self::Node => Some(Tool::Node),
+            Self::Python | Self::Python3 => Some(Tool::Python),
+            Self::Rust => Some(Tool::Rust),
+            Self::Go => Some(Tool::Go),
+            Self::Ruby => Some(Tool::Ruby),
+            Self::Php => Some(Tool::Php),
+            Self::Perl => Some(Tool::Perl),
+            Self::Java => Some(Tool::Java),
+            _ => None,
+        }
+    }
+
+    /// the first installed binary meeting the minimum version
+    pub async fn toolchain(&self) -> Option<Toolchain> {
+        for binary in self.get_binaries() {
+            if let Some(toolchain) = Toolchain::discover_min(&binary, self.get_min_version()).await
+            {
+                return Some(toolchain);
+            }
+        }
+
+        None
+    }
+
     pub async fn is_available(&self) -> Result<bool, anyhow::Error> {
         match self {
             Self::Unsupported => Err(anyhow!(
                 "cannot check availability for unsupported file type"
             )),
-            // if python3 is not available, we can use python
-            Self::Python | Self::Python3 => Ok(Command::new(Self::Python3.to_string())
-                .arg("--version")
-                .output()
-                .await
-                .is_ok()
-                || Command::new(Self::Python.to_string())
-                    .arg("--version")
-                    .output()
-                    .await
-                    .is_ok()),
-
-            _ => Ok(Command::new(self.to_string())
-                .arg("--version")
-                .output()
-                .await
-                .is_ok()),
+            _ => Ok(self.toolchain().await.is_some()),
         }
     }
 
+    /// absolute path of the binary to use, or its bare name when it's not installed
     pub async fn get_command_name(&self) -> Result<String, anyhow::Error> {
         match self {
             Self::Unsupported => Err(anyhow!("cannot get command name for unsupported file type")),
-            Self::Python3 => {
-                if self.is_available().await.is_ok() {
-                    return Ok("python3".to_string());
-                }
-
-                Ok(Self::Python.to_string())
-            }
-            _ => Ok(self.to_string()),
+            _ => match self.toolchain().await {
+                Some(toolchain) => Ok(toolchain.path.display().to_string()),
+                None => Ok(self.to_string()),
+            },
         }
     }
 }
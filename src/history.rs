@@ -0,0 +1,268 @@
+use crate::{
+    core::{RunOutcome, RunStatus},
+    diff,
+    utils::{format_bytes, format_table, project_cache_dir},
+};
+use anyhow::anyhow;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// runs older than the last `LIMIT` are removed from the history
+const LIMIT: usize = 200;
+
+const NOT_RECORDED: &str = "the output wasn't recorded, see --record-output";
+
+/// A recorded run: the source that ran and what it did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub number: usize,
+    /// seconds since the unix epoch
+    pub time: u64,
+    pub path: PathBuf,
+    pub source: String,
+    /// stdout and stderr, or the build errors when the build failed,
+    /// none when the output went straight to the terminal
+    pub output: Option<String>,
+    pub status: RunStatus,
+    pub build_duration: Duration,
+    pub run_duration: Duration,
+    pub image: Option<String>,
+    pub peak_memory: Option<u64>,
+}
+
+/// The runs of the current directory, one json file each in its cache directory
+pub struct History {
+    dir: PathBuf,
+}
+
+impl History {
+    pub fn open() -> anyhow::Result<Self> {
+        Ok(Self {
+            dir: project_cache_dir("history", &std::env::current_dir()?)?,
+        })
+    }
+
+    /// saves the run of `source`, the content `path` had when it ran
+    pub fn record(&self, path: &Path, source: String, outcome: &RunOutcome) -> anyhow::Result<Run> {
+        let numbers = self.numbers()?;
+
+        let output = match outcome.status {
+            RunStatus::BuildFailed => Some(
+                outcome
+                    .diagnostics
+                    .iter()
+                    .map(|d| d.message.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            _ => outcome.output.clone(),
+        };
+
+        let run = Run {
+            number: numbers.last().map_or(1, |n| n + 1),
+            time: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            path: std::fs::canonicalize(path).unwrap_or(path.to_path_buf()),
+            source,
+            output,
+            status: outcome.status,
+            build_duration: outcome.build_duration,
+            run_duration: outcome.run_duration,
+            image: outcome.image.clone(),
+            peak_memory: outcome.usage.map(|u| u.peak_memory()),
+        };
+
+        std::fs::write(self.file(run.number), serde_json::to_string(&run)?)?;
+
+        for old in numbers.iter().rev().skip(LIMIT - 1) {
+            log::debug!("removing run #{old} from the history");
+            std::fs::remove_file(self.file(*old))?;
+        }
+
+        Ok(run)
+    }
+
+    pub fn get(&self, number: usize) -> anyhow::Result<Run> {
+        let content = std::fs::read_to_string(self.file(number))
+            .map_err(|_| anyhow!("no run #{number} in the history"))?;
+
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// every recorded run, oldest first
+    pub fn runs(&self) -> anyhow::Result<Vec<Run>> {
+        self.numbers()?.into_iter().map(|n| self.get(n)).collect()
+    }
+
+    fn file(&self, number: usize) -> PathBuf {
+        self.dir.join(format!("{number}.json"))
+    }
+
+    /// numbers of the recorded runs, in order
+    fn numbers(&self) -> anyhow::Result<Vec<usize>> {
+        let mut numbers = vec![];
+        for entry in std::fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            if let Some(number) = name
+                .to_str()
+                .and_then(|n| n.strip_suffix(".json"))
+                .and_then(|n| n.parse().ok())
+            {
+                numbers.push(number);
+            }
+        }
+
+        numbers.sort();
+        Ok(numbers)
+    }
+}
+
+/// lists the runs recorded in the current directory
+pub fn list() -> anyhow::Result<()> {
+    let runs = History::open()?.runs()?;
+    if runs.is_empty() {
+        eprintln!("No runs recorded in this directory yet");
+        return Ok(());
+    }
+
+    let mut rows = vec![["#", "when", "file", "exit", "build", "run", "memory"]
+        .map(String::from)
+        .to_vec()];
+
+    for run in &runs {
+        rows.push(vec![
+            run.number.to_string(),
+            ago(run.time),
+            file_name(&run.path),
+            run.status.to_string(),
+            if run.build_duration.is_zero() {
+                "-".to_string()
+            } else {
+                format!("{:.2?}", run.build_duration)
+            },
+            format!("{:.2?}", run.run_duration),
+            run.peak_memory.map_or("-".to_string(), format_bytes),
+        ]);
+    }
+
+    let lines = format_table(&rows);
+    println!("{}", lines[0].bold());
+    for line in &lines[1..] {
+        println!("{line}");
+    }
+
+    Ok(())
+}
+
+/// prints the source and output of run `number`
+pub fn show(number: usize) -> anyhow::Result<()> {
+    let run = History::open()?.get(number)?;
+
+    eprintln!(
+        "{}",
+        format!(
+            "Run #{} of {}, {}",
+            run.number,
+            run.path.display(),
+            ago(run.time)
+        )
+        .bold()
+    );
+    eprintln!(
+        "🏁 Run taken: {} [{:?} build, {:?} run]",
+        format!("{:?}", run.build_duration + run.run_duration).dimmed(),
+        run.build_duration,
+        run.run_duration
+    );
+    if run.status != RunStatus::Success {
+        eprintln!("💥 Exited with: {}", run.status.to_string().red());
+    }
+
+    if let Some(image) = &run.image {
+        eprintln!("📦 Image: {image}");
+    }
+
+    if let Some(peak) = run.peak_memory {
+        eprintln!("🧠 Peak memory: {}", format_bytes(peak));
+    }
+
+    eprintln!();
+    eprintln!("{}", "── source ──".bold());
+    println!("{}", run.source.trim_end());
+    eprintln!();
+    eprintln!("{}", "── output ──".bold());
+    match &run.output {
+        Some(output) => println!("{}", output.trim_end()),
+        None => eprintln!("{}", NOT_RECORDED.dimmed()),
+    }
+
+    Ok(())
+}
+
+/// prints how the source and the output changed from run `old` to run `new`
+pub fn diff(old: usize, new: usize) -> anyhow::Result<()> {
+    let history = History::open()?;
+    let (old, new) = (history.get(old)?, history.get(new)?);
+
+    if old.path != new.path {
+        eprintln!(
+            "⚠️ #{} ran {} and #{} ran {}",
+            old.number,
+            old.path.display(),
+            new.number,
+            new.path.display()
+        );
+    }
+
+    for (name, a, b) in [
+        ("source", Some(&old.source), Some(&new.source)),
+        ("output", old.output.as_ref(), new.output.as_ref()),
+    ] {
+        eprintln!(
+            "{}",
+            format!("── {name} #{} → #{} ──", old.number, new.number).bold()
+        );
+
+        let (Some(a), Some(b)) = (a, b) else {
+            eprintln!("{}", NOT_RECORDED.dimmed());
+            eprintln!();
+            continue;
+        };
+
+        if !diff::render(a, b, 3) {
+            eprintln!("{}", "no changes".dimmed());
+        }
+
+        eprintln!();
+    }
+
+    if old.status != new.status {
+        eprintln!("💥 Exit changed: {} → {}", old.status, new.status);
+    }
+
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// how long ago the unix `time` was, e.g. `5m ago`
+fn ago(time: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(time, |d| d.as_secs());
+    let secs = now.saturating_sub(time);
+
+    match secs {
+        0..=59 => format!("{secs}s ago"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
@@ -0,0 +1,49 @@
+//! Detects how a file runs and runs it, locally or in docker.
+//!
+//! This is what the `runner` command is built on, [`Runner`] runs a file once
+//! and reports what happened as a [`RunOutcome`] and a stream of [`Event`]s.
+
+pub mod core;
+pub mod event;
+pub mod file_type;
+
+// the rest is what the `runner` command needs, not part of the library's api
+#[doc(hidden)]
+pub mod bench;
+#[doc(hidden)]
+pub mod command;
+#[doc(hidden)]
+pub mod daemon;
+#[doc(hidden)]
+pub mod diagnostic;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod explain;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod pin;
+#[doc(hidden)]
+pub mod proxy;
+#[doc(hidden)]
+pub mod runtime;
+#[doc(hidden)]
+pub mod templates;
+#[doc(hidden)]
+pub mod toolchain;
+#[doc(hidden)]
+pub mod usage;
+#[doc(hidden)]
+pub mod utils;
+
+pub use crate::{
+    command::docker::DockerImage,
+    core::{RunOptions, RunOutcome, RunStatus, Runner},
+    diagnostic::{Diagnostic, Severity},
+    event::{Event, Events},
+    explain::{Backend, Explanation},
+    file_type::FileType,
+    proxy::Port,
+    usage::Usage,
+};
@@ -0,0 +1,98 @@
+use colored::*;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Writes runner's log records to stderr, or to the `--log-file`
+struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+    start: Instant,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // the records of dependencies, like the file watcher's, are left out
+        metadata.level() <= self.level && metadata.target().starts_with("runner")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let elapsed = self.start.elapsed().as_secs_f64();
+
+        match &self.file {
+            Some(file) => {
+                // the file outlives the session, so the records get the time of day
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0.0, |d| d.as_secs_f64());
+
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(
+                        file,
+                        "{now:.3} {:>8.3}s {:<5} {}: {}",
+                        elapsed,
+                        record.level(),
+                        record.target(),
+                        record.args()
+                    );
+                }
+            }
+            None => eprintln!(
+                "{:>8.3}s {} {}",
+                elapsed,
+                label(record.level()),
+                record.args()
+            ),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(Ok(mut file)) = self.file.as_ref().map(|f| f.lock()) {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn label(level: Level) -> ColoredString {
+    let label = format!("{level:<5}");
+    match level {
+        Level::Error => label.red(),
+        Level::Warn => label.yellow(),
+        Level::Info => label.green(),
+        Level::Debug => label.blue(),
+        Level::Trace => label.dimmed(),
+    }
+}
+
+/// logs `-v` debug and `-vv` trace records, to `file` when given,
+/// which gets debug records even without `-v`
+pub fn init(verbose: u8, file: Option<&Path>) -> anyhow::Result<()> {
+    let level = match (verbose, file) {
+        (0, None) => LevelFilter::Warn,
+        (0 | 1, _) => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    let file = file
+        .map(|path| File::options().create(true).append(true).open(path))
+        .transpose()?
+        .map(Mutex::new);
+
+    log::set_boxed_logger(Box::new(Logger {
+        level,
+        file,
+        start: Instant::now(),
+    }))?;
+    log::set_max_level(level);
+
+    Ok(())
+}
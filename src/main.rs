@@ -1,20 +1,26 @@
+mod bisect;
 mod cli;
-mod command;
-mod core;
-mod file_type;
-mod templates;
-mod utils;
-
-use std::io::{stdin, stdout, Write};
-use termion::event::Key;
-use termion::input::TermRead;
-use termion::raw::IntoRawMode;
+mod compare;
+mod control;
+mod doctor;
+mod logger;
+mod matrix;
+mod serve;
+mod up;
 
 use crate::{
-    cli::Args,
-    core::{run, DURATION_ZERO},
-    file_type::FileType,
-    utils::{clear_screen, temp_file},
+    cli::{Args, Commands, Format, RunArgs},
+    control::{Call, Request, Server},
+};
+use runner::{
+    bench,
+    core::{RunOptions, DURATION_ZERO},
+    diagnostic, diff,
+    event::{Event as RunnerEvent, Events},
+    history::{self, History},
+    runtime::go,
+    utils::{clear_screen, format_bytes, temp_file},
+    FileType, RunOutcome, RunStatus, Runner,
 };
 
 use anyhow::anyhow;
@@ -24,22 +30,84 @@ use notify::{
     event::{DataChange, ModifyKind},
     Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
 };
+use serde_json::Value;
+use std::{future::Future, path::Path, time::Duration};
 use tokio::{select, sync::mpsc};
 
+/// exit codes of `--once` that aren't the program's
+const EXIT_BUILD_FAILED: i32 = 123;
+const EXIT_TIMEOUT: i32 = 124;
+const EXIT_ERROR: i32 = 125;
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    logger::init(args.verbose, args.log_file.as_deref())?;
+
+    let args = match args.subcommand {
+        Some(Commands::Run(run)) => RunArgs { once: true, ..run },
+        Some(Commands::Explain(explain)) => RunArgs {
+            dry_run: true,
+            ..explain
+        },
+        Some(Commands::Doctor) => return doctor::run().await,
+        Some(Commands::Compare(compare)) => return compare::run(&compare).await,
+        Some(Commands::History) => return history::list(),
+        Some(Commands::Show { run }) => return history::show(run),
+        Some(Commands::Diff { old, new }) => return history::diff(old, new),
+        Some(Commands::Bisect(bisect)) => return bisect::run(&bisect).await,
+        Some(Commands::Up(up)) => return up::run(&up).await,
+        None => args.run,
+    };
+
+    if args.dry_run {
+        return explain(&args).await;
+    }
+
+    if args.serve {
+        return serve::run(&args).await;
+    }
+
+    if !args.once {
+        watch(&args).await?;
+        return Ok(());
+    }
+
+    // runner's own failures are told apart from the program's
+    let code = watch(&args).await.unwrap_or_else(|e| {
+        eprintln!("Error: {e:#}");
+        EXIT_ERROR
+    });
+
+    std::process::exit(code);
+}
+
+/// watches the file and runs it on every change, or runs it once with `--once`,
+/// returns the exit code of the last run
+async fn watch(args: &RunArgs) -> anyhow::Result<i32> {
+    let mut options = args.run_options()?;
+    let events = Events::new(args.format == Format::Json);
+    let mut server = args
+        .socket
+        .as_deref()
+        .map(|socket| Server::bind(socket, events.subscribers()))
+        .transpose()?;
+
     let mut last_run_duration = DURATION_ZERO;
+    let mut last_peak_memory = None;
+    let mut last_output: Option<String> = None;
+    let mut last_outcome: Option<RunOutcome> = None;
+    let mut exit_code = 0;
 
     let runtime = args.runtime.clone().unwrap_or(FileType::Typescript);
     let path = args.path.clone().unwrap_or(temp_file(&runtime).await?);
     let is_temp = args.path.is_none();
 
     if args.path.is_some() && args.runtime.is_some() {
-        println!("🚨 Both path and runtime are provided, ignoring runtime");
+        eprintln!("🚨 Both path and runtime are provided, ignoring runtime");
     }
 
-    let file_type = FileType::try_from(&path)?;
+    let mut file_type = FileType::try_from(&path)?;
 
     let (tx, mut rx) = mpsc::channel(10);
     let mut watcher = RecommendedWatcher::new(
@@ -49,17 +117,29 @@ async fn main() -> anyhow::Result<()> {
         notify::Config::default(),
     )?;
 
-    watcher.watch(&path, RecursiveMode::Recursive)?;
+    // a go package spans every file in its directory, the file is one of them
+    let watch_package = matches!(file_type, FileType::Go) && go::find_module_root(&path).is_some();
+    if watch_package {
+        watcher.watch(&go::package_dir(&path), RecursiveMode::NonRecursive)?;
+        log::debug!(
+            "watching the go package {}",
+            go::package_dir(&path).display()
+        );
+    } else {
+        watcher.watch(&path, RecursiveMode::Recursive)?;
+        log::debug!("watching {}", path.display());
+    }
 
-    clear_screen();
-    eprintln!(
-        "🏃 Watching {} for changes...",
-        &path
-            .to_str()
-            .ok_or(anyhow!("unable to retrive path"))?
-            .yellow()
-    );
-    eprintln!();
+    if !args.once {
+        clear_screen();
+        watching(&path)?;
+        eprintln!();
+    }
+
+    events.emit(RunnerEvent::WatchStarted {
+        path: path.clone(),
+        runtime: file_type.to_string(),
+    });
 
     // tokio::spawn(async move {
     //     loop {
@@ -79,87 +159,365 @@ async fn main() -> anyhow::Result<()> {
     //     }
     // });
 
+    let mut ran = false;
+    let mut rerun = false;
+
     loop {
-        select! {
-            res = rx.recv() => {
-                let event = res.ok_or(anyhow!("Failed to receive event"))??;
-                let kind = event.kind;
-
-                match kind {
-                    EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(DataChange::Content)) => {
-                        clear_screen();
-
-                        eprintln!("🏃File Changed!");
-                        eprintln!();
-
-
-                        let image = args.image.clone().map(|i| i.parse().unwrap());
-
-                        let (
-                            build_duration,
-                            run_duration,
-                            docker_image
-                        ) = run(
-                            &file_type,
-                            args.no_docker,
-                            args.command.clone(),
-                            args.env.clone(),
-                            &path,
-                            image
-                        ).await?;
-
-                        let elapsed = run_duration + build_duration;
-                        let time_taken = format!("{:?}", elapsed).dimmed();
-
-                        eprintln!();
-                        eprintln!(
-                            "🏁 Run taken: {} [{:?} build, {:?} run]",
-                            time_taken, build_duration, run_duration
-                        );
-
-                        let delta = if last_run_duration.gt(&elapsed) {
-                            last_run_duration - elapsed
-                        } else {
-                            elapsed - last_run_duration
-                        };
-
-                        let deltastring = if last_run_duration.lt(&elapsed) && !delta.is_zero() {
-                            format!("+{:?}", delta).red()
-                        } else {
-                            format!("-{:?}", delta).green()
-                        };
-
-                        eprintln!("⏱️ Delta: {}", deltastring);
-
-                        if let Some(image) = docker_image {
-                            eprintln!("📦 Image: {}", image);
-                        }
-
-                        eprintln!(
-                            "🏃 Watching {} for changes...",
-                            &path
-                                .to_str()
-                                .ok_or(anyhow!("unable to retrive path"))?
-                                .yellow()
-                        );
-
-                        last_run_duration = elapsed;
+        if args.once {
+            if ran {
+                break;
+            }
+        } else if !rerun {
+            select! {
+                res = rx.recv() => {
+                    let event = res.ok_or(anyhow!("Failed to receive event"))??;
+                    log::trace!("watcher event {:?} on {:?}", event.kind, event.paths);
+
+                    if watch_package && !event.paths.iter().any(|p| go::is_package_file(p)) {
+                        continue;
+                    }
+
+                    if !matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(DataChange::Content))
+                    ) {
+                        continue;
+                    }
+
+                    log::debug!("{} changed", path.display());
+                    clear_screen();
+
+                    eprintln!("🏃File Changed!");
+                    eprintln!();
+
+                    events.emit(RunnerEvent::ChangeDetected { path: path.clone() });
+                }
+
+                Some(call) = control::next_call(&mut server) => {
+                    answer(call, &mut file_type, &mut options, &last_outcome, &mut rerun);
+                    if !rerun {
+                        continue;
                     }
-                    _ => {}
                 }
+
+                _ = tokio::signal::ctrl_c() => {
+                    eprintln!("🧼 Cleaning up...");
+                    break;
+                }
+            }
+        }
+
+        if rerun {
+            rerun = false;
+            clear_screen();
+
+            eprintln!("🔁 Rerun requested");
+            eprintln!();
+        }
+
+        ran = true;
+
+        // the calls answered during a run change the runs after it
+        let (running_type, running_options) = (file_type.clone(), options.clone());
+
+        if let Some(matrix) = &args.matrix {
+            let run = matrix.run(&running_type, &path, &running_options);
+            let Some(outcomes) = answering(
+                run,
+                &mut server,
+                &mut file_type,
+                &mut options,
+                &last_outcome,
+                &mut rerun,
+            )
+            .await
+            else {
+                watching(&path)?;
+                continue;
+            };
+            let outcomes = outcomes?;
+
+            eprintln!();
+            matrix.render(&outcomes);
+
+            exit_code = outcomes
+                .iter()
+                .map(|o| exit_code_of(o.status))
+                .find(|code| *code != 0)
+                .unwrap_or(0);
+
+            if !args.once {
+                watching(&path)?;
+            }
+            continue;
+        }
+
+        if let Some(runs) = args.bench {
+            let run = bench::run(
+                &running_type,
+                &path,
+                &running_options,
+                runs,
+                args.warmup,
+                args.save_baseline,
+            );
+            let Some(result) = answering(
+                run,
+                &mut server,
+                &mut file_type,
+                &mut options,
+                &last_outcome,
+                &mut rerun,
+            )
+            .await
+            else {
+                watching(&path)?;
+                continue;
+            };
+            result?;
+
+            if !args.once {
+                watching(&path)?;
+            }
+            continue;
+        }
+
+        // read before running, the file may change again while it runs
+        let source = std::fs::read_to_string(&path).unwrap_or_default();
+
+        let mut runner = Runner::new(&path)
+            .runtime(running_type)
+            .options(running_options)
+            .events(events.clone());
+        if let Some(secs) = args.timeout {
+            runner = runner.timeout(Duration::from_secs_f64(secs));
+        }
+
+        let Some(outcome) = answering(
+            runner.run(),
+            &mut server,
+            &mut file_type,
+            &mut options,
+            &last_outcome,
+            &mut rerun,
+        )
+        .await
+        else {
+            watching(&path)?;
+            continue;
+        };
+        let outcome = outcome?;
+
+        exit_code = exit_code_of(outcome.status);
+
+        if outcome.status == RunStatus::TimedOut {
+            eprintln!();
+            eprintln!("⏰ Timed out after {:?}", outcome.run_duration);
+
+            if !args.once {
+                watching(&path)?;
             }
+            continue;
+        }
 
-            _ = tokio::signal::ctrl_c() => {
-                println!("🧼 Cleaning up...");
+        let build_duration = outcome.build_duration;
+        let run_duration = outcome.run_duration;
 
-                if is_temp {
-                    tokio::fs::remove_file(&path).await?;
+        if let (Some(mode), Some(output)) = (args.output_diff, &outcome.output) {
+            diff::render_output(last_output.as_deref(), output, mode);
+            last_output = Some(output.clone());
+        }
+
+        eprintln!();
+        diagnostic::render(&outcome.diagnostics);
+
+        if outcome.status == RunStatus::BuildFailed {
+            eprintln!(
+                "🏁 Build taken: {}",
+                format!("{:?}", build_duration).dimmed()
+            );
+        } else {
+            let elapsed = run_duration + build_duration;
+            let time_taken = format!("{:?}", elapsed).dimmed();
+
+            eprintln!(
+                "🏁 Run taken: {} [{:?} build, {:?} run]",
+                time_taken, build_duration, run_duration
+            );
+
+            if let RunStatus::Failed(_) | RunStatus::Killed(_) = outcome.status {
+                eprintln!("💥 Exited with: {}", outcome.status.to_string().red());
+            }
+
+            let delta = if last_run_duration.gt(&elapsed) {
+                last_run_duration - elapsed
+            } else {
+                elapsed - last_run_duration
+            };
+
+            let deltastring = if last_run_duration.lt(&elapsed) && !delta.is_zero() {
+                format!("+{:?}", delta).red()
+            } else {
+                format!("-{:?}", delta).green()
+            };
+
+            eprintln!("⏱️ Delta: {}", deltastring);
+
+            last_run_duration = elapsed;
+
+            if let Some(usage) = outcome.usage {
+                eprintln!("📈 Usage: {}", usage.to_string().dimmed());
+
+                let peak = usage.peak_memory();
+                if let Some(last) = last_peak_memory {
+                    let deltastring = if peak > last {
+                        format!("+{}", format_bytes(peak - last)).red()
+                    } else {
+                        format!("-{}", format_bytes(last - peak)).green()
+                    };
+
+                    eprintln!("🧠 Memory delta: {}", deltastring);
                 }
 
-                break;
+                last_peak_memory = Some(peak);
+            }
+        }
+
+        if let Some(image) = &outcome.image {
+            eprintln!("📦 Image: {}", image);
+        }
+
+        if !args.no_history {
+            match History::open().and_then(|h| h.record(&path, source, &outcome)) {
+                Ok(run) => eprintln!("📜 Recorded as run #{}", run.number),
+                Err(e) => eprintln!("⚠️ Failed to record the run: {e}"),
             }
         }
+
+        last_outcome = Some(outcome);
+
+        if !args.once {
+            watching(&path)?;
+        }
+    }
+
+    if is_temp {
+        log::debug!("removing the temporary file {}", path.display());
+        tokio::fs::remove_file(&path).await?;
+    }
+
+    if let Some(socket) = &args.socket {
+        log::debug!("removing the socket {}", socket.display());
+        let _ = std::fs::remove_file(socket);
+    }
+
+    Ok(exit_code)
+}
+
+/// prints what running the file would do, as json with `--format json`
+async fn explain(args: &RunArgs) -> anyhow::Result<()> {
+    let path = args
+        .path
+        .as_ref()
+        .ok_or(anyhow!("there is no file to explain, pass its path"))?;
+
+    let explanation = Runner::new(path)
+        .options(args.run_options()?)
+        .explain()
+        .await?;
+
+    match args.format {
+        Format::Text => explanation.render(),
+        Format::Json => println!("{}", serde_json::to_string(&explanation)?),
     }
 
     Ok(())
 }
+
+/// drives `run` to its end while answering control calls, `None` when a client cancels it
+async fn answering<T>(
+    run: impl Future<Output = T>,
+    server: &mut Option<Server>,
+    file_type: &mut FileType,
+    options: &mut RunOptions,
+    last_outcome: &Option<RunOutcome>,
+    rerun: &mut bool,
+) -> Option<T> {
+    tokio::pin!(run);
+
+    loop {
+        select! {
+            result = &mut run => return Some(result),
+
+            Some(call) = control::next_call(server) => {
+                if let Request::Cancel = call.request {
+                    call.reply(Value::Null);
+                    eprintln!();
+                    eprintln!("🛑 Run cancelled");
+                    return None;
+                }
+
+                answer(call, file_type, options, last_outcome, rerun);
+            }
+        }
+    }
+}
+
+/// answers a control call, `rerun` is set when it asks for one
+fn answer(
+    call: Call,
+    file_type: &mut FileType,
+    options: &mut RunOptions,
+    last_outcome: &Option<RunOutcome>,
+    rerun: &mut bool,
+) {
+    match &call.request {
+        Request::Rerun => {
+            *rerun = true;
+            call.reply(Value::Null);
+        }
+        // nothing is running
+        Request::Cancel => call.reply(Value::Null),
+        Request::SetRuntime(runtime) => {
+            eprintln!("🔀 Running as {runtime} from now on");
+            *file_type = runtime.clone();
+            call.reply(Value::String(file_type.to_string()));
+        }
+        Request::SetDocker(enabled) => {
+            options.no_docker = !enabled.unwrap_or(options.no_docker);
+            if options.no_docker {
+                eprintln!("🐳 Running locally from now on");
+            } else {
+                eprintln!("🐳 Running in docker from now on");
+            }
+            call.reply(Value::Bool(!options.no_docker));
+        }
+        Request::LastOutcome => {
+            call.reply(serde_json::to_value(last_outcome).unwrap_or_default());
+        }
+    }
+}
+
+/// the exit code `--once` exits with after a run
+fn exit_code_of(status: RunStatus) -> i32 {
+    match status {
+        RunStatus::Success => 0,
+        RunStatus::BuildFailed => EXIT_BUILD_FAILED,
+        RunStatus::Failed(Some(code)) => code,
+        // killed by a signal, like shells report it
+        RunStatus::Failed(None) => 128,
+        RunStatus::Killed(signal) => 128 + signal,
+        RunStatus::TimedOut => EXIT_TIMEOUT,
+    }
+}
+
+fn watching(path: &Path) -> anyhow::Result<()> {
+    eprintln!(
+        "🏃 Watching {} for changes...",
+        path.to_str()
+            .ok_or(anyhow!("unable to retrive path"))?
+            .yellow()
+    );
+
+    Ok(())
+}
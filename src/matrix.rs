@@ -0,0 +1,162 @@
+use crate::compare;
+use anyhow::{anyhow, bail};
+use runner::{
+    command::docker::{self, DockerImage},
+    core::{docker_setup, run_in_docker, RunOptions, RunOutcome},
+    file_type::FileType,
+    pin::Tool,
+};
+use std::{path::Path, str::FromStr};
+use tokio::task::JoinSet;
+
+/// Versions of a runtime to run the same file against, e.g. `python=3.9,3.12`
+#[derive(Debug, Clone)]
+pub struct Matrix {
+    pub tool: Tool,
+    pub versions: Vec<String>,
+}
+
+impl FromStr for Matrix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tool, versions) = s.split_once('=').ok_or(anyhow!(
+            "expected <runtime>=<version>,..., e.g. python=3.9,3.12"
+        ))?;
+
+        let versions: Vec<String> = versions
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect();
+
+        if versions.is_empty() {
+            bail!("no versions given for '{tool}'");
+        }
+
+        Ok(Self {
+            tool: tool.trim().parse()?,
+            versions,
+        })
+    }
+}
+
+impl Matrix {
+    /// the image of every version, in order
+    pub fn images(&self) -> anyhow::Result<Vec<DockerImage>> {
+        self.versions
+            .iter()
+            .map(|version| {
+                self.tool
+                    .docker_image(version)
+                    .ok_or(anyhow!("no {} image for version '{version}'", self.tool))
+            })
+            .collect()
+    }
+
+    /// runs `path` in one container per version, concurrently,
+    /// the outcomes are in the order of the versions and hold the captured output
+    pub async fn run(
+        &self,
+        file_type: &FileType,
+        path: &Path,
+        options: &RunOptions,
+    ) -> anyhow::Result<Vec<RunOutcome>> {
+        if file_type.pin_tool().is_some_and(|tool| tool != self.tool) {
+            bail!(
+                "a {} matrix can't run .{} files",
+                self.tool,
+                file_type.get_extension()
+            );
+        }
+
+        let images = self.images()?;
+        pull_missing(&images).await?;
+
+        let options = RunOptions {
+            capture: true,
+            ..options.clone()
+        };
+
+        let mut tasks = JoinSet::new();
+
+        for (i, image) in images.into_iter().enumerate() {
+            let setup = docker_setup(
+                file_type,
+                path,
+                options.command.clone(),
+                Some(image),
+                &options.crates,
+            )
+            .await?;
+
+            let file_type = file_type.clone();
+            let path = path.to_path_buf();
+            let options = options.clone();
+
+            tasks.spawn(async move {
+                let outcome = run_in_docker(&file_type, &path, &setup, &options).await;
+                (i, outcome)
+            });
+        }
+
+        let mut outcomes = vec![RunOutcome::default(); self.versions.len()];
+
+        while let Some(joined) = tasks.join_next().await {
+            let (i, outcome) = joined?;
+            outcomes[i] = outcome?;
+        }
+
+        Ok(outcomes)
+    }
+
+    /// prints the output of every version, then a table with one column per version
+    pub fn render(&self, outcomes: &[RunOutcome]) {
+        let labels: Vec<String> = self
+            .versions
+            .iter()
+            .map(|v| format!("{} {v}", self.tool))
+            .collect();
+
+        compare::render(&labels, outcomes);
+    }
+}
+
+/// pulls the images that are not on the machine yet, once the user agrees
+async fn pull_missing(images: &[DockerImage]) -> anyhow::Result<()> {
+    let mut missing = vec![];
+    for image in images {
+        if !docker::image_exists_on_machine(&image.to_string()).await? {
+            missing.push(image.to_string());
+        }
+    }
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("Images not installed: {}", missing.join(", "));
+
+    if !inquire::prompt_confirmation("Would you like to install them?")? {
+        bail!("the matrix needs every image to run");
+    }
+
+    for image in missing {
+        let mut s = spinners::Spinner::new(spinners::Spinners::Dots, format!("Pulling {image}..."));
+        let result = docker::pull(&image).await?;
+
+        if !result.status.success() {
+            s.stop_with_symbol("✖");
+            bail!(
+                "Failed to pull image: '{}'\n {}",
+                image,
+                String::from_utf8_lossy(&result.stderr)
+            );
+        }
+
+        s.stop_with_symbol("✔");
+    }
+
+    Ok(())
+}
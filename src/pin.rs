@@ -0,0 +1,402 @@
+use crate::{
+    command::docker::DockerImage,
+    toolchain::{Toolchain, Version},
+};
+use anyhow::anyhow;
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// A tool whose version projects pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Node,
+    Bun,
+    Deno,
+    Python,
+    Rust,
+    Go,
+    Ruby,
+    Php,
+    Perl,
+    Java,
+}
+
+impl Tool {
+    pub const ALL: [Self; 10] = [
+        Self::Node,
+        Self::Bun,
+        Self::Deno,
+        Self::Python,
+        Self::Rust,
+        Self::Go,
+        Self::Ruby,
+        Self::Php,
+        Self::Perl,
+        Self::Java,
+    ];
+
+    /// the binary whose version is compared with the pin
+    pub fn binary(&self) -> &'static str {
+        match self {
+            Self::Node => "node",
+            Self::Bun => "bun",
+            Self::Deno => "deno",
+            Self::Python => "python3",
+            Self::Rust => "rustc",
+            Self::Go => "go",
+            Self::Ruby => "ruby",
+            Self::Php => "php",
+            Self::Perl => "perl",
+            Self::Java => "java",
+        }
+    }
+
+    /// the official image tagged for `version`, `None` when there is no tag for it
+    pub fn docker_image(&self, version: &str) -> Option<DockerImage> {
+        let version = version.trim_start_matches('v');
+        let numeric = numbers(version).is_some();
+
+        let (image, tag) = match self {
+            Self::Node => ("node", format!("{}-alpine", node_tag(version, numeric)?)),
+            // `openjdk-17.0.2` and `temurin-17` in asdf, `1.8` for java 8
+            Self::Java => {
+                let version = dir_version(version)?;
+                let major = match version.major {
+                    1 => version.minor,
+                    major => major,
+                };
+                ("openjdk", major.to_string())
+            }
+            _ if !numeric => return None,
+            Self::Bun => ("oven/bun", format!("{version}-alpine")),
+            Self::Deno => ("denoland/deno", format!("alpine-{version}")),
+            Self::Python => ("python", format!("{version}-alpine")),
+            Self::Go => ("golang", format!("{version}-alpine")),
+            Self::Rust => ("rust", format!("{version}-alpine")),
+            Self::Ruby => ("ruby", format!("{version}-alpine")),
+            Self::Php => ("php", format!("{version}-alpine")),
+            Self::Perl => ("perl", version.to_string()),
+        };
+
+        Some(DockerImage::new(image, &tag))
+    }
+
+    /// names in `.tool-versions` and `mise.toml`, asdf and mise disagree for node and go
+    fn names(&self) -> &'static [&'static str] {
+        match self {
+            Self::Node => &["nodejs", "node"],
+            Self::Go => &["golang", "go"],
+            Self::Bun => &["bun"],
+            Self::Deno => &["deno"],
+            Self::Python => &["python"],
+            Self::Rust => &["rust"],
+            Self::Ruby => &["ruby"],
+            Self::Php => &["php"],
+            Self::Perl => &["perl"],
+            Self::Java => &["java"],
+        }
+    }
+
+    /// files pinning only this tool, they win over `mise.toml` and `.tool-versions`
+    fn version_files(&self) -> &'static [&'static str] {
+        match self {
+            Self::Node => &[".nvmrc", ".node-version"],
+            Self::Python => &[".python-version"],
+            Self::Rust => &["rust-toolchain.toml", "rust-toolchain"],
+            Self::Go => &[".go-version"],
+            Self::Ruby => &[".ruby-version"],
+            Self::Java => &[".java-version"],
+            _ => &[],
+        }
+    }
+
+    /// directories with one entry per installed version, and where the binaries are inside an entry
+    fn install_roots(&self) -> Vec<(PathBuf, &'static str)> {
+        let mut roots = vec![];
+
+        for name in self.names() {
+            roots.push((
+                data_dir("MISE_DATA_DIR", "~/.local/share/mise")
+                    .join("installs")
+                    .join(name),
+                "bin",
+            ));
+            roots.push((
+                data_dir("ASDF_DATA_DIR", "~/.asdf")
+                    .join("installs")
+                    .join(name),
+                if *name == "golang" { "go/bin" } else { "bin" },
+            ));
+        }
+
+        match self {
+            Self::Node => roots.push((
+                data_dir("NVM_DIR", "~/.nvm").join("versions").join("node"),
+                "bin",
+            )),
+            Self::Python => {
+                roots.push((data_dir("PYENV_ROOT", "~/.pyenv").join("versions"), "bin"))
+            }
+            Self::Ruby => roots.push((data_dir("RBENV_ROOT", "~/.rbenv").join("versions"), "bin")),
+            // `go install golang.org/dl/go1.22.1` downloads to `~/sdk/go1.22.1`
+            Self::Go => roots.push((
+                PathBuf::from(shellexpand::tilde("~/sdk").to_string()),
+                "bin",
+            )),
+            Self::Rust => roots.push((
+                data_dir("RUSTUP_HOME", "~/.rustup").join("toolchains"),
+                "bin",
+            )),
+            Self::Java => roots.push((
+                data_dir("SDKMAN_DIR", "~/.sdkman")
+                    .join("candidates")
+                    .join("java"),
+                "bin",
+            )),
+            _ => {}
+        }
+
+        roots
+    }
+}
+
+impl FromStr for Tool {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|tool| tool.names().contains(&s))
+            .ok_or(anyhow!("unknown runtime: '{s}'"))
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.names().last().unwrap_or(&"unknown"))
+    }
+}
+
+/// A version pinned by a project file such as `.nvmrc` or `.tool-versions`
+#[derive(Debug, Clone)]
+pub struct Pin {
+    pub tool: Tool,
+    /// as written in the file, e.g. `20`, `v20.11.1`, `lts/iron` or `stable`
+    pub version: String,
+    pub source: PathBuf,
+}
+
+/// How a pin is satisfied on this machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// the toolchain on the `PATH` is used: it has the pinned version,
+    /// or the pin is an alias that can't be checked (`lts/iron`, `stable`)
+    Path,
+    /// the pinned version is installed off the `PATH`, holds its `bin` directory
+    Installed(PathBuf),
+    /// no installed version matches, holds the version on the `PATH`, if any
+    Missing(Option<Version>),
+}
+
+impl Pin {
+    /// the nearest pin for `tool`, walking up from `path`
+    pub fn find(tool: Tool, path: &Path) -> Option<Self> {
+        let path = std::fs::canonicalize(path).ok()?;
+
+        for dir in path.ancestors().skip(1) {
+            let files =
+                tool.version_files()
+                    .iter()
+                    .chain(&[".mise.toml", "mise.toml", ".tool-versions"]);
+
+            for file in files {
+                let source = dir.join(file);
+                let Ok(content) = std::fs::read_to_string(&source) else {
+                    continue;
+                };
+
+                let version = match *file {
+                    ".tool-versions" => parse_tool_versions(&content, tool.names()),
+                    ".mise.toml" | "mise.toml" => parse_mise(&content, tool.names()),
+                    "rust-toolchain.toml" | "rust-toolchain" => parse_rust_toolchain(&content),
+                    _ => first_word(&content),
+                };
+
+                if let Some(version) = version {
+                    return Some(Self {
+                        tool,
+                        version,
+                        source,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// whether `version` satisfies the pin, `20` is satisfied by `20.11.1`
+    pub fn matches(&self, version: Version) -> bool {
+        let Some(numbers) = self.numbers() else {
+            return false;
+        };
+
+        numbers
+            .iter()
+            .zip([version.major, version.minor, version.patch])
+            .all(|(pinned, actual)| *pinned == actual)
+    }
+
+    /// checks the toolchain on the `PATH`, then the install directories of
+    /// mise, asdf, nvm, pyenv, rbenv, rustup, sdkman and go's `~/sdk`
+    pub async fn resolve(&self) -> Resolution {
+        let active = Toolchain::discover(self.tool.binary())
+            .await
+            .and_then(|t| t.version);
+
+        if active.is_some_and(|v| self.matches(v)) {
+            return Resolution::Path;
+        }
+
+        match self.install_dir() {
+            Some(dir) => Resolution::Installed(dir),
+            None if self.numbers().is_none() => Resolution::Path,
+            None => Resolution::Missing(active),
+        }
+    }
+
+    /// the image running the pinned version, `None` when there is no tag for it
+    pub fn docker_image(&self) -> Option<DockerImage> {
+        self.tool.docker_image(&self.version)
+    }
+
+    fn numbers(&self) -> Option<Vec<u32>> {
+        numbers(&self.version)
+    }
+
+    /// `bin` directory of the newest installed version matching the pin
+    fn install_dir(&self) -> Option<PathBuf> {
+        let mut found = vec![];
+
+        for (root, bin) in self.tool.install_roots() {
+            let Ok(entries) = std::fs::read_dir(&root) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let version = dir_version(&name);
+
+                let matched = match version {
+                    Some(version) if self.numbers().is_some() => self.matches(version),
+                    // aliases match by name, `stable` matches rustup's `stable-x86_64-unknown-linux-gnu`
+                    _ => name == self.version || name.starts_with(&format!("{}-", self.version)),
+                };
+
+                let dir = entry.path().join(bin);
+                if matched && dir.is_dir() {
+                    found.push((version, dir));
+                }
+            }
+        }
+
+        found.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+        found.into_iter().next().map(|(_, dir)| dir)
+    }
+}
+
+/// `[20, 11]` for `v20.11`, `None` for aliases
+fn numbers(version: &str) -> Option<Vec<u32>> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// `$var`, or `default` when it's not set
+fn data_dir(var: &str, default: &str) -> PathBuf {
+    let dir = env::var(var).unwrap_or_else(|_| default.to_string());
+
+    PathBuf::from(shellexpand::tilde(&dir).to_string())
+}
+
+/// the version in an install directory name, e.g. `v20.11.1`, `go1.22.1` or `1.75.0-x86_64-unknown-linux-gnu`
+fn dir_version(name: &str) -> Option<Version> {
+    let start = name.find(|c: char| c.is_ascii_digit())?;
+    let version: String = name[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+
+    version.trim_end_matches('.').parse().ok()
+}
+
+/// node images are tagged with versions and lts codenames, `lts/iron` runs on `iron-alpine`
+fn node_tag(version: &str, numeric: bool) -> Option<String> {
+    if numeric {
+        return Some(version.to_string());
+    }
+
+    match version.strip_prefix("lts") {
+        Some("" | "/*") => Some("lts".to_string()),
+        Some(codename) => codename.strip_prefix('/').map(str::to_lowercase),
+        None => None,
+    }
+}
+
+/// first word of the first line that's not blank or a comment, as in `.nvmrc` and `.python-version`
+fn first_word(content: &str) -> Option<String> {
+    content
+        .lines()
+        .filter_map(|line| line.split('#').next()?.split_whitespace().next())
+        .next()
+        .map(String::from)
+}
+
+/// the `nodejs 20.11.1` lines of asdf, fallback versions after the first are ignored
+fn parse_tool_versions(content: &str, names: &[&str]) -> Option<String> {
+    content.lines().find_map(|line| {
+        let mut words = line.split('#').next()?.split_whitespace();
+        let name = words.next()?;
+
+        names
+            .contains(&name)
+            .then(|| words.next().map(String::from))
+            .flatten()
+    })
+}
+
+/// the `[tools]` of mise: `node = "20"`, `node = ["20", "18"]` or `node = { version = "20" }`
+fn parse_mise(content: &str, names: &[&str]) -> Option<String> {
+    let config: toml::Table = content.parse().ok()?;
+    let tools = config.get("tools")?.as_table()?;
+
+    names.iter().find_map(|name| {
+        let value = match tools.get(*name)? {
+            toml::Value::Array(versions) => versions.first()?,
+            value => value,
+        };
+
+        match value {
+            toml::Value::String(version) => Some(version.clone()),
+            toml::Value::Table(table) => table.get("version")?.as_str().map(String::from),
+            _ => None,
+        }
+    })
+}
+
+/// `toolchain.channel` of `rust-toolchain.toml`, or the bare channel of a legacy `rust-toolchain`
+fn parse_rust_toolchain(content: &str) -> Option<String> {
+    match content.parse::<toml::Table>() {
+        Ok(config) => config
+            .get("toolchain")?
+            .get("channel")?
+            .as_str()
+            .map(String::from),
+        Err(_) => first_word(content),
+    }
+}
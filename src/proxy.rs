@@ -0,0 +1,486 @@
+use anyhow::{anyhow, bail};
+use base64::Engine;
+use std::{fmt, net::SocketAddr, str::FromStr, sync::Arc};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    select,
+    sync::{broadcast, RwLock},
+};
+
+/// where the live reload client connects its websocket
+const LIVE_RELOAD_PATH: &str = "/__runner/livereload";
+
+/// reloads the page when the proxy says so, injected into the html pages
+const LIVE_RELOAD_CLIENT: &str = "<script>new WebSocket(`ws://${location.host}/__runner/livereload`).onmessage = () => location.reload();</script>";
+
+/// A port to reach the program on, `8080` or `8080:80` for port 80 of the program behind 8080
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Port {
+    /// `None` lets docker pick a free port of localhost
+    pub host: Option<u16>,
+    /// the port the program listens on, the host port when not given
+    pub target: Option<u16>,
+}
+
+impl Port {
+    /// the port the program listens on in a container, which has ports of its own
+    pub fn container(&self) -> u16 {
+        self.target.or(self.host).unwrap_or_default()
+    }
+
+    /// the value of `docker run -p`
+    pub fn publish(&self) -> String {
+        match self.host {
+            Some(host) => format!("{host}:{}", self.container()),
+            None => format!("127.0.0.1::{}", self.container()),
+        }
+    }
+}
+
+impl FromStr for Port {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let port = |p: &str| {
+            p.parse::<u16>()
+                .map_err(|_| anyhow!("'{p}' is not a port, expected e.g. 8080 or 8080:80"))
+        };
+
+        match s.split_once(':') {
+            Some((host, target)) => Ok(Self {
+                host: Some(port(host)?),
+                target: Some(port(target)?),
+            }),
+            None => Ok(Self {
+                host: Some(port(s)?),
+                target: None,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Port {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.host, self.target) {
+            (Some(host), Some(target)) => write!(f, "{host}:{target}"),
+            (Some(host), None) => write!(f, "{host}"),
+            (None, _) => write!(f, ":{}", self.container()),
+        }
+    }
+}
+
+/// Forwards the connections to a port of localhost to the current version of a server,
+/// so the port stays the same across restarts
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    upstream: Arc<RwLock<Option<SocketAddr>>>,
+    /// tells the pages to reload, when the html goes through the live reload client
+    reload: Option<broadcast::Sender<()>>,
+}
+
+impl Proxy {
+    /// listens on `port`, connections are closed until there is an upstream,
+    /// with `live_reload` html pages get a client that reloads them on `Proxy::reload`
+    pub async fn bind(port: u16, live_reload: bool) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(|e| anyhow!("unable to listen on port {port}: {e}"))?;
+
+        let proxy = Self {
+            upstream: Arc::new(RwLock::new(None)),
+            reload: live_reload.then(|| broadcast::channel(16).0),
+        };
+
+        let (upstream, reload) = (proxy.upstream.clone(), proxy.reload.clone());
+        tokio::spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let address = *upstream.read().await;
+
+                match (&reload, address) {
+                    (Some(reload), _) => {
+                        let reload = reload.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = forward_http(client, address, reload).await {
+                                log::debug!("proxying a request to {address:?} failed: {e}");
+                            }
+                        });
+                    }
+                    (None, Some(address)) => {
+                        tokio::spawn(forward(client, address));
+                    }
+                    (None, None) => {
+                        log::debug!("closing a connection to port {port}, nothing is serving")
+                    }
+                }
+            }
+        });
+
+        Ok(proxy)
+    }
+
+    /// where the next connections go, the open ones stay with the previous upstream
+    pub async fn set_upstream(&self, address: Option<SocketAddr>) {
+        log::debug!("proxying to {address:?}");
+        *self.upstream.write().await = address;
+    }
+
+    /// reloads the pages opened through the proxy, without live reload it does nothing
+    pub fn reload(&self) {
+        if let Some(reload) = &self.reload {
+            log::debug!("reloading {} pages", reload.receiver_count());
+            let _ = reload.send(());
+        }
+    }
+}
+
+async fn forward(mut client: TcpStream, address: SocketAddr) {
+    match TcpStream::connect(address).await {
+        Ok(mut server) => {
+            let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+        }
+        Err(e) => log::debug!("unable to connect to {address}: {e}"),
+    }
+}
+
+/// The head of an http request or response
+struct Head {
+    /// the request or status line
+    start: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Head {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn has_token(&self, name: &str, token: &str) -> bool {
+        self.header(name).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case(token))
+        })
+    }
+
+    fn remove(&mut self, names: &[&str]) {
+        self.headers
+            .retain(|(key, _)| !names.iter().any(|name| key.eq_ignore_ascii_case(name)));
+    }
+
+    fn set(&mut self, name: &str, value: impl Into<String>) {
+        self.remove(&[name]);
+        self.headers.push((name.to_string(), value.into()));
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!("{}\r\n", self.start);
+        for (key, value) in &self.headers {
+            head.push_str(&format!("{key}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+        head.into_bytes()
+    }
+}
+
+/// reads the head off `stream`, along with the start of the body read with it
+async fn read_head(stream: &mut (impl AsyncRead + Unpin)) -> anyhow::Result<(Head, Vec<u8>)> {
+    let mut buf = vec![];
+    let end = loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        if buf.len() > 64 * 1024 {
+            bail!("the http head is over 64KiB");
+        }
+
+        let mut chunk = [0; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            bail!("the connection closed before the end of the http head");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let rest = buf.split_off(end + 4);
+    let head = String::from_utf8_lossy(&buf[..end]).to_string();
+    let mut lines = head.split("\r\n");
+
+    Ok((
+        Head {
+            start: lines.next().unwrap_or_default().to_string(),
+            headers: lines
+                .filter_map(|line| line.split_once(':'))
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .collect(),
+        },
+        rest,
+    ))
+}
+
+/// forwards one http request, injecting the live reload client into html responses,
+/// or serves the live reload websocket
+async fn forward_http(
+    mut client: TcpStream,
+    address: Option<SocketAddr>,
+    reload: broadcast::Sender<()>,
+) -> anyhow::Result<()> {
+    let (mut request, body) = read_head(&mut client).await?;
+    let mut start = request.start.split_whitespace();
+    let (method, target) = (
+        start.next().unwrap_or_default(),
+        start.next().unwrap_or_default(),
+    );
+
+    if target == LIVE_RELOAD_PATH {
+        return live_reload(client, &request, reload.subscribe()).await;
+    }
+
+    let Some(address) = address else {
+        client
+            .write_all(
+                b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await?;
+        return Ok(());
+    };
+
+    let head_request = method == "HEAD";
+    let mut server = TcpStream::connect(address).await?;
+
+    // the program's own websockets go through untouched
+    if request.has_token("connection", "upgrade") {
+        server.write_all(&request.to_bytes()).await?;
+        server.write_all(&body).await?;
+        tokio::io::copy_bidirectional(&mut client, &mut server).await?;
+        return Ok(());
+    }
+
+    // one request per connection and an uncompressed body, so the html can be rewritten
+    request.remove(&["accept-encoding", "keep-alive"]);
+    request.set("Connection", "close");
+    server.write_all(&request.to_bytes()).await?;
+    server.write_all(&body).await?;
+
+    let (mut client_read, mut client_write) = client.into_split();
+    let (mut server_read, mut server_write) = server.into_split();
+    tokio::spawn(async move {
+        let _ = tokio::io::copy(&mut client_read, &mut server_write).await;
+    });
+
+    let (mut response, body) = read_head(&mut server_read).await?;
+    let status = response.start.split_whitespace().nth(1).unwrap_or_default();
+    let html = response
+        .header("content-type")
+        .is_some_and(|t| t.starts_with("text/html"));
+    // some servers compress anyway, their body can't be rewritten
+    let encoded = response
+        .header("content-encoding")
+        .is_some_and(|e| !e.eq_ignore_ascii_case("identity"));
+
+    if !html || encoded || head_request || matches!(status, "204" | "304") {
+        client_write.write_all(&response.to_bytes()).await?;
+        client_write.write_all(&body).await?;
+        tokio::io::copy(&mut server_read, &mut client_write).await?;
+        return Ok(());
+    }
+
+    let body = read_body(&mut server_read, &response, body).await?;
+    let body = inject(&body);
+    response.remove(&["transfer-encoding", "content-encoding"]);
+    response.set("Content-Length", body.len().to_string());
+    response.set("Connection", "close");
+
+    client_write.write_all(&response.to_bytes()).await?;
+    client_write.write_all(&body).await?;
+    Ok(())
+}
+
+/// reads the rest of the body of `head` off `stream`, after the `start` read with the head,
+/// without waiting for the connection to close when its length is known
+async fn read_body(
+    stream: &mut (impl AsyncRead + Unpin),
+    head: &Head,
+    mut body: Vec<u8>,
+) -> anyhow::Result<Vec<u8>> {
+    let chunked = head.has_token("transfer-encoding", "chunked");
+    let length = match head.header("content-length") {
+        Some(length) => Some(
+            length
+                .parse::<usize>()
+                .map_err(|_| anyhow!("'{length}' is not a content length"))?,
+        ),
+        None => None,
+    };
+
+    let mut chunk = [0; 8192];
+    loop {
+        if chunked {
+            if let Some(dechunked) = dechunk(&body) {
+                return Ok(dechunked);
+            }
+        } else if let Some(length) = length {
+            if body.len() >= length {
+                body.truncate(length);
+                return Ok(body);
+            }
+        }
+
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            if chunked || length.is_some() {
+                bail!("the connection closed before the end of the body");
+            }
+            // without a length the body ends with the connection
+            return Ok(body);
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// the body of a `Transfer-Encoding: chunked` response, `None` until it's complete
+fn dechunk(mut chunked: &[u8]) -> Option<Vec<u8>> {
+    let mut body = vec![];
+    loop {
+        let line = chunked.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&chunked[..line]).ok()?;
+        // the size can be followed by `;extensions`
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        chunked = &chunked[line + 2..];
+
+        if size == 0 {
+            return Some(body);
+        }
+        body.extend_from_slice(chunked.get(..size)?);
+        chunked = chunked.get(size + 2..)?;
+    }
+}
+
+/// `html` with the live reload client before `</body>`, or at the end without one
+fn inject(html: &[u8]) -> Vec<u8> {
+    let lower = html.to_ascii_lowercase();
+    let at = lower
+        .windows(7)
+        .rposition(|w| w == b"</body>")
+        .unwrap_or(html.len());
+
+    let mut injected = html[..at].to_vec();
+    injected.extend_from_slice(LIVE_RELOAD_CLIENT.as_bytes());
+    injected.extend_from_slice(&html[at..]);
+    injected
+}
+
+/// accepts the websocket of the live reload client, sending it `reload` on every reload
+async fn live_reload(
+    mut client: TcpStream,
+    request: &Head,
+    mut reload: broadcast::Receiver<()>,
+) -> anyhow::Result<()> {
+    let key = request
+        .header("sec-websocket-key")
+        .ok_or(anyhow!("the live reload request is not a websocket"))?;
+
+    // the accept key the websocket protocol derives from the request's key
+    let digest =
+        sha1_smol::Sha1::from(format!("{key}258EAFA5-E914-47DA-95CA-C5AB0DC85B11")).digest();
+    let accept = base64::engine::general_purpose::STANDARD.encode(digest.bytes());
+
+    client
+        .write_all(
+            format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    let mut incoming = [0; 1024];
+    loop {
+        select! {
+            res = reload.recv() => {
+                if let Err(broadcast::error::RecvError::Closed) = res {
+                    return Ok(());
+                }
+                // a final text frame of 6 bytes, servers don't mask theirs
+                client.write_all(b"\x81\x06reload").await?;
+            }
+
+            // what the page sends doesn't matter, only that it's gone
+            n = client.read(&mut incoming) => {
+                if n? == 0 {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// a port of localhost nothing listens on, for a server to take
+pub fn free_port() -> std::io::Result<u16> {
+    Ok(std::net::TcpListener::bind(("127.0.0.1", 0))?
+        .local_addr()?
+        .port())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dechunk_joins_the_chunks_and_skips_extensions() {
+        let chunked = b"5;name=value\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n";
+        assert_eq!(dechunk(chunked).as_deref(), Some(&b"hello, world"[..]));
+    }
+
+    #[test]
+    fn dechunk_waits_for_the_last_chunk() {
+        assert_eq!(dechunk(b"5\r\nhello\r\n"), None);
+        assert_eq!(dechunk(b"5\r\nhel"), None);
+        assert_eq!(dechunk(b"zz\r\nhello\r\n0\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn inject_goes_before_the_last_closing_body_tag() {
+        let html = inject(b"<body><p>&lt;/body&gt;</p></BODY></html>");
+        let expected = format!("<body><p>&lt;/body&gt;</p>{LIVE_RELOAD_CLIENT}</BODY></html>");
+        assert_eq!(html, expected.as_bytes());
+    }
+
+    #[test]
+    fn inject_appends_without_a_body_tag() {
+        let html = inject(b"<h1>hi</h1>");
+        assert_eq!(html, format!("<h1>hi</h1>{LIVE_RELOAD_CLIENT}").as_bytes());
+    }
+
+    #[tokio::test]
+    async fn read_head_parses_the_headers_and_keeps_the_start_of_the_body() {
+        let mut stream: &[u8] =
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nConnection: keep-alive, Upgrade\r\n\r\n<html>";
+        let (head, body) = read_head(&mut stream).await.unwrap();
+
+        assert_eq!(head.start, "HTTP/1.1 200 OK");
+        assert_eq!(head.header("content-type"), Some("text/html"));
+        assert!(head.has_token("connection", "upgrade"));
+        assert_eq!(body, b"<html>");
+    }
+
+    #[tokio::test]
+    async fn read_head_fails_when_the_connection_closes_early() {
+        let mut stream: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n";
+        assert!(read_head(&mut stream).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn read_body_stops_at_the_content_length() {
+        let mut stream: &[u8] = b"llo, world and what comes after";
+        let (head, start) = read_head(&mut &b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\nhe"[..])
+            .await
+            .unwrap();
+
+        let body = read_body(&mut stream, &head, start).await.unwrap();
+        assert_eq!(body, b"hello, world");
+    }
+}
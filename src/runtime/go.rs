@@ -0,0 +1,272 @@
+use crate::{
+    core::BuildOutput,
+    file_type::FileType,
+    utils::{cache_dir, project_cache_dir, write_if_changed},
+};
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// where the module is mounted when running inside docker
+const DOCKER_MODULE_DIR: &str = "/root/module";
+
+/// where a generated module keeps the imports it was last tidied with
+const IMPORTS_FILE: &str = "imports";
+
+/// default `GOMODCACHE` and `GOCACHE` of the official golang images
+const DOCKER_MODCACHE_DIR: &str = "/go/pkg/mod";
+const DOCKER_BUILDCACHE_DIR: &str = "/root/.cache/go-build";
+
+/// The go module a file belongs to,
+/// either the enclosing `go.mod` or a module generated for standalone files
+#[derive(Debug, Clone)]
+pub struct GoModule {
+    /// directory containing `go.mod`
+    pub root: PathBuf,
+    /// directory of the package to run
+    pub package: PathBuf,
+    /// whether the module was generated by runner
+    pub generated: bool,
+    source: PathBuf,
+}
+
+impl GoModule {
+    pub async fn resolve(path: &Path) -> anyhow::Result<Self> {
+        if let Some(root) = find_module_root(path) {
+            return Ok(Self {
+                root,
+                package: package_dir(path),
+                generated: false,
+                source: path.to_path_buf(),
+            });
+        }
+
+        let root = project_cache_dir("go", path)?;
+        let source = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("unable to read '{}'", path.display()))?;
+
+        if !root.join("go.mod").exists() {
+            write_if_changed(&root.join("go.mod"), &generated_go_mod().await).await?;
+        }
+
+        write_if_changed(&root.join("main.go"), &source).await?;
+
+        Ok(Self {
+            package: root.clone(),
+            root,
+            generated: true,
+            source: path.to_path_buf(),
+        })
+    }
+
+    /// builds the package into `out`
+    /// generated modules are tidied first when their imports changed so third-party imports get resolved
+    pub async fn build(
+        &self,
+        envs: &[(String, String)],
+        out: &Path,
+    ) -> anyhow::Result<BuildOutput> {
+        if self.generated && self.needs_tidy().await {
+            let tidy = BuildOutput::capture(
+                &FileType::Go,
+                Command::new("go")
+                    .envs(envs.iter().cloned())
+                    .current_dir(&self.root)
+                    .args(["mod", "tidy"]),
+            )
+            .await?;
+
+            if !tidy.success {
+                return Ok(tidy);
+            }
+
+            let source = tokio::fs::read_to_string(&self.source).await?;
+            write_if_changed(&self.root.join(IMPORTS_FILE), &imports(&source).join("\n")).await?;
+        }
+
+        let mut build = BuildOutput::capture(
+            &FileType::Go,
+            Command::new("go")
+                .envs(envs.iter().cloned())
+                .current_dir(&self.package)
+                .arg("build")
+                .arg("-o")
+                .arg(out)
+                .arg("."),
+        )
+        .await?;
+
+        // go reports paths relative to the package, point them back to the watched files
+        for diagnostic in build.diagnostics.iter_mut() {
+            if let Some(file) = diagnostic.file.as_mut() {
+                *file = if self.generated {
+                    self.source.display().to_string()
+                } else {
+                    self.package
+                        .join(file.trim_start_matches("./"))
+                        .display()
+                        .to_string()
+                };
+            }
+        }
+
+        Ok(build)
+    }
+
+    /// whether the imports of a generated module changed since it was last tidied,
+    /// or its third-party ones have no `go.sum` yet, tidying on every build would query the module proxy
+    async fn needs_tidy(&self) -> bool {
+        let Ok(source) = tokio::fs::read_to_string(&self.source).await else {
+            return true;
+        };
+
+        let imports = imports(&source);
+        let tidied = tokio::fs::read_to_string(self.root.join(IMPORTS_FILE))
+            .await
+            .ok();
+
+        tidied != Some(imports.join("\n"))
+            || (imports.iter().any(|i| is_third_party(i)) && !self.root.join("go.sum").exists())
+    }
+
+    /// volumes needed to run the module inside the golang docker image,
+    /// the module and build caches are shared between runs
+    pub fn docker_volumes(&self) -> Vec<String> {
+        let cache = cache_dir().join("go");
+
+        vec![
+            format!("{}:{DOCKER_MODULE_DIR}", self.root.display()),
+            format!("{}:{DOCKER_MODCACHE_DIR}", cache.join("mod").display()),
+            format!("{}:{DOCKER_BUILDCACHE_DIR}", cache.join("build").display()),
+        ]
+    }
+
+    pub fn docker_command(&self) -> String {
+        if self.generated {
+            return format!("cd {DOCKER_MODULE_DIR} && go mod tidy && go run .");
+        }
+
+        let package = std::fs::canonicalize(&self.package).unwrap_or(self.package.clone());
+        let relative = package.strip_prefix(&self.root).unwrap_or(Path::new(""));
+
+        format!("cd {DOCKER_MODULE_DIR}/{} && go run .", relative.display())
+    }
+}
+
+/// the `go.mod` of a generated module, its `go` directive is the version of the installed toolchain
+async fn generated_go_mod() -> String {
+    let mut go_mod = "module runner.local/main\n".to_string();
+
+    if let Some(version) = FileType::Go.toolchain().await.and_then(|t| t.version) {
+        go_mod.push_str(&format!("\ngo {}.{}\n", version.major, version.minor));
+    }
+
+    go_mod
+}
+
+/// the sorted paths imported by a go file
+fn imports(source: &str) -> Vec<String> {
+    let quoted = |line: &str| {
+        let (_, rest) = line.split_once('"')?;
+        let (import, _) = rest.split_once('"')?;
+        Some(import.to_string())
+    };
+
+    let mut imports = vec![];
+    let mut in_block = false;
+
+    for line in source.lines().map(str::trim) {
+        if in_block {
+            if line.starts_with(')') {
+                in_block = false;
+            } else {
+                imports.extend(quoted(line));
+            }
+        } else if let Some(rest) = line
+            .strip_prefix("import")
+            .filter(|rest| rest.starts_with([' ', '\t', '(', '"']))
+        {
+            let rest = rest.trim_start();
+            if let Some(first) = rest.strip_prefix('(') {
+                in_block = !first.contains(')');
+                imports.extend(quoted(first));
+            } else {
+                imports.extend(quoted(rest));
+            }
+        }
+    }
+
+    imports.sort();
+    imports.dedup();
+    imports
+}
+
+/// whether `import` comes from a module rather than the standard library
+fn is_third_party(import: &str) -> bool {
+    import
+        .split('/')
+        .next()
+        .is_some_and(|first| first.contains('.'))
+}
+
+/// finds the directory of the closest `go.mod`, walking up from `path`
+pub fn find_module_root(path: &Path) -> Option<PathBuf> {
+    let path = std::fs::canonicalize(path).ok()?;
+
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.join("go.mod").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// whether a change to `path` should trigger a run of a module package
+pub fn is_package_file(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "go")
+        || path
+            .file_name()
+            .is_some_and(|n| n == "go.mod" || n == "go.sum")
+}
+
+pub fn package_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_of_single_and_grouped_declarations() {
+        let source = r#"package main
+
+import "fmt"
+import str "strings"
+
+import (
+	"os"
+	color "github.com/fatih/color"
+	_ "embed"
+)
+
+func main() {
+	important := "import \"not/this\""
+	fmt.Println(important)
+}
+"#;
+
+        assert_eq!(
+            imports(source),
+            ["embed", "fmt", "github.com/fatih/color", "os", "strings"]
+        );
+    }
+
+    #[test]
+    fn third_party_imports_have_a_domain() {
+        assert!(is_third_party("github.com/fatih/color"));
+        assert!(!is_third_party("net/http"));
+    }
+}
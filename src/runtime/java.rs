@@ -0,0 +1,189 @@
+use crate::{
+    core::BuildOutput,
+    file_type::FileType,
+    toolchain::Toolchain,
+    utils::{project_cache_dir, write_if_changed},
+};
+use anyhow::Context;
+use std::path::Path;
+use tokio::process::Command;
+
+/// first JDK able to launch single-file source programs (JEP 330)
+const SOURCE_LAUNCH_VERSION: u32 = 11;
+
+/// major version of the installed JDK, e.g. `8` for `1.8.0_392` and `17` for `17.0.2`
+pub async fn jdk_version() -> Option<u32> {
+    let version = Toolchain::discover("java").await?.version?;
+
+    match version.major {
+        1 => Some(version.minor),
+        major => Some(major),
+    }
+}
+
+/// whether `java File.java` can be used instead of compiling first
+pub async fn supports_source_launch() -> bool {
+    jdk_version()
+        .await
+        .is_some_and(|v| v >= SOURCE_LAUNCH_VERSION)
+}
+
+/// fully qualified name of the class to run:
+/// the first public top-level type, or the first top-level type if none is public
+pub fn main_class(source: &str) -> Option<String> {
+    let tokens = tokenize(source);
+
+    let mut package = None;
+    let mut first = None;
+    let mut depth = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.as_str() {
+            "{" => depth += 1,
+            "}" => depth -= 1,
+            "package" if depth == 0 => {
+                package = Some(
+                    tokens[i + 1..]
+                        .iter()
+                        .take_while(|t| *t != ";")
+                        .map(String::as_str)
+                        .collect::<String>(),
+                );
+            }
+            "class" | "record" | "enum" | "interface" if depth == 0 => {
+                let Some(name) = tokens.get(i + 1) else {
+                    continue;
+                };
+
+                // modifiers between the start of the declaration and the keyword
+                let public = tokens[..i]
+                    .iter()
+                    .rev()
+                    .take_while(|t| !matches!(t.as_str(), ";" | "}" | "{"))
+                    .any(|t| t == "public");
+
+                if public {
+                    first = Some(name.clone());
+                    break;
+                }
+
+                first.get_or_insert(name.clone());
+            }
+            _ => {}
+        }
+    }
+
+    let class = first?;
+
+    Some(match package {
+        Some(package) => format!("{package}.{class}"),
+        None => class,
+    })
+}
+
+/// compiles `path` with `javac` into the cache directory,
+/// returning the build result and the command running the main class
+pub async fn compile(
+    path: &Path,
+    env: &[(String, String)],
+) -> anyhow::Result<(BuildOutput, Command)> {
+    let source = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("unable to read '{}'", path.display()))?;
+
+    let class = main_class(&source).unwrap_or("Main".to_string());
+    let out = project_cache_dir("java", path)?;
+
+    // javac wants a public class in a file named after it, which the watched file may not be
+    let file = class.rsplit('.').next().unwrap_or(&class);
+    let copy = out.join("src").join(format!("{file}.java"));
+    write_if_changed(&copy, &source).await?;
+
+    let build = BuildOutput::capture(
+        &FileType::Java,
+        Command::new("javac")
+            .envs(env.iter().cloned())
+            .arg("-d")
+            .arg(&out)
+            .arg(&copy),
+    )
+    .await?;
+
+    let mut command = Command::new("java");
+    command.arg("-cp").arg(&out).arg(class);
+
+    Ok((build, command))
+}
+
+/// launches the mounted source directly on JDK 11+,
+/// otherwise copies it to a file named after the class (as javac requires) and compiles it
+///
+/// the command ends with the launch so program arguments can be appended to it
+pub fn docker_command(path: &Path, entrypoint: &str) -> String {
+    let class = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| main_class(&s))
+        .unwrap_or("Main".to_string());
+
+    let file = class.rsplit('.').next().unwrap_or(&class);
+
+    format!(
+        "if java -version 2>&1 | grep -qE '\"(1\\.[0-9]+|9|10)[.\"]'; then \
+            mkdir -p /tmp/src && cp /root/app/{entrypoint} /tmp/src/{file}.java && \
+            javac -d /tmp/out /tmp/src/{file}.java || exit 1; \
+            set -- java -cp /tmp/out {class}; \
+        else set -- java /root/app/{entrypoint}; fi; exec \"$@\""
+    )
+}
+
+/// splits java source into identifiers and punctuation, skipping comments and literals
+fn tokenize(source: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            '"' | '\'' => {
+                let mut escaped = false;
+                for next in chars.by_ref() {
+                    if next == c && !escaped {
+                        break;
+                    }
+                    escaped = next == '\\' && !escaped;
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '$' => {
+                let mut ident = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_alphanumeric() || next == '_' || next == '$') {
+                        break;
+                    }
+                    ident.push(next);
+                    chars.next();
+                }
+                tokens.push(ident);
+            }
+            c if c.is_whitespace() => {}
+            c => tokens.push(c.to_string()),
+        }
+    }
+
+    tokens
+}
@@ -0,0 +1,5 @@
+pub mod go;
+pub mod java;
+pub mod python;
+pub mod rust;
+pub mod typescript;
@@ -0,0 +1,102 @@
+use crate::toolchain::which;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// The environment a python file should run in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PythonEnv {
+    /// a virtualenv, holds the path of its interpreter
+    Venv(PathBuf),
+    /// a uv project, holds the project directory
+    Uv(PathBuf),
+    /// a poetry project, holds the project directory
+    Poetry(PathBuf),
+}
+
+impl PythonEnv {
+    /// the nearest project environment, walking up from `path`,
+    /// falling back to the activated virtualenv (`$VIRTUAL_ENV`)
+    pub fn detect(path: &Path) -> Option<Self> {
+        let path = std::fs::canonicalize(path).ok()?;
+
+        for dir in path.ancestors().skip(1) {
+            for venv in [".venv", "venv"] {
+                let interpreter = interpreter(&dir.join(venv));
+                if interpreter.is_file() {
+                    return Some(Self::Venv(interpreter));
+                }
+            }
+
+            if dir.join("uv.lock").is_file() && which("uv").is_some() {
+                return Some(Self::Uv(dir.to_path_buf()));
+            }
+
+            if dir.join("poetry.lock").is_file() && which("poetry").is_some() {
+                return Some(Self::Poetry(dir.to_path_buf()));
+            }
+        }
+
+        std::env::var("VIRTUAL_ENV")
+            .ok()
+            .map(|venv| interpreter(Path::new(&venv)))
+            .filter(|interpreter| interpreter.is_file())
+            .map(Self::Venv)
+    }
+
+    fn command(&self, path: &Path) -> Command {
+        match self {
+            Self::Venv(interpreter) => {
+                let mut command = Command::new(interpreter);
+                command.arg("-u").arg(path);
+                command
+            }
+            Self::Uv(project) => {
+                let mut command = Command::new("uv");
+                command
+                    .arg("run")
+                    .arg("--project")
+                    .arg(project)
+                    .args(["python", "-u"])
+                    .arg(path);
+                command
+            }
+            Self::Poetry(project) => {
+                let mut command = Command::new("poetry");
+                command
+                    .arg("--directory")
+                    .arg(project)
+                    .args(["run", "python", "-u"])
+                    .arg(path);
+                command
+            }
+        }
+    }
+}
+
+/// command running `path` in its project environment, or with `default` when there is none
+///
+/// output is unbuffered so prints show up live instead of when the program exits
+pub fn command(path: &Path, default: &str) -> Command {
+    let env = PythonEnv::detect(path);
+    log::debug!("python environment of {}: {env:?}", path.display());
+
+    let mut command = match env {
+        Some(env) => env.command(path),
+        None => {
+            let mut command = Command::new(default);
+            command.arg("-u").arg(path);
+            command
+        }
+    };
+
+    command.env("PYTHONUNBUFFERED", "1");
+    command
+}
+
+fn interpreter(venv: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv.join("Scripts").join("python.exe")
+    } else {
+        venv.join("bin").join("python")
+    }
+}
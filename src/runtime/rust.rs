@@ -0,0 +1,244 @@
+use crate::{
+    core::BuildOutput,
+    diagnostic::Diagnostic,
+    file_type::FileType,
+    utils::{cache_dir, project_cache_dir, write_if_changed},
+};
+use anyhow::{anyhow, Context};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+const DEFAULT_EDITION: &str = "2021";
+
+/// where the generated project is mounted when running inside docker
+pub const DOCKER_PROJECT_DIR: &str = "/root/project";
+
+/// where cargo keeps its registry inside the official rust images
+const DOCKER_REGISTRY_DIR: &str = "/usr/local/cargo/registry";
+
+/// A cargo project generated around a single rust file,
+/// cached between runs so dependencies are only compiled once
+#[derive(Debug, Clone)]
+pub struct CargoProject {
+    pub dir: PathBuf,
+    pub name: String,
+    source: PathBuf,
+}
+
+impl CargoProject {
+    /// generates (or refreshes) the cached project for `path`
+    /// dependencies are read from the embedded manifest and from `crates` (`name@version`)
+    pub async fn prepare(path: &Path, crates: &[String]) -> anyhow::Result<Self> {
+        let source = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("unable to read '{}'", path.display()))?;
+
+        let (manifest, code) = extract_manifest(&source);
+        let name = package_name(path);
+        let manifest = build_manifest(&name, manifest.as_deref(), crates)?;
+
+        let dir = project_cache_dir("rust", path)?;
+
+        write_if_changed(&dir.join("Cargo.toml"), &manifest).await?;
+        write_if_changed(&dir.join("src").join("main.rs"), &code).await?;
+
+        Ok(Self {
+            dir,
+            name,
+            source: path.to_path_buf(),
+        })
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        self.dir.join("Cargo.toml")
+    }
+
+    pub fn binary_path(&self) -> PathBuf {
+        self.dir.join("target").join("debug").join(&self.name)
+    }
+
+    /// volumes needed to build the project inside the rust docker image
+    pub fn docker_volumes(&self) -> Vec<String> {
+        let registry = cache_dir().join("rust").join("registry");
+
+        vec![
+            format!("{}:{DOCKER_PROJECT_DIR}", self.dir.display()),
+            format!("{}:{DOCKER_REGISTRY_DIR}", registry.display()),
+        ]
+    }
+
+    pub async fn build(&self, envs: &[(String, String)]) -> anyhow::Result<BuildOutput> {
+        let mut build = BuildOutput::capture(
+            &FileType::Rust,
+            Command::new("cargo")
+                .envs(envs.iter().cloned())
+                .arg("build")
+                .arg("--manifest-path")
+                .arg(self.manifest_path())
+                .args(["--message-format", "json", "--color", "never"]),
+        )
+        .await
+        .map_err(|e| anyhow!("failed to run cargo: {e}"))?;
+
+        // point the diagnostics back to the watched file
+        let source = self.source.display().to_string();
+        for diagnostic in build.diagnostics.iter_mut() {
+            if diagnostic.file.as_deref() == Some("src/main.rs") {
+                diagnostic.file = Some(source.clone());
+            }
+        }
+
+        // errors that don't point to the source (e.g. a missing crate)
+        // come as plain text, keep only the relevant lines
+        for diagnostic in build.diagnostics.iter_mut().filter(|d| d.file.is_none()) {
+            if let Some(error) = diagnostic.message.lines().find(|l| l.starts_with("error")) {
+                *diagnostic = Diagnostic::error(error.trim_start_matches("error: "));
+            }
+        }
+
+        Ok(build)
+    }
+}
+
+/// splits the embedded manifest from the code
+///
+/// supports both the frontmatter syntax:
+/// ```text
+/// ---cargo
+/// [dependencies]
+/// serde = "1"
+/// ---
+/// ```
+/// and the `//! ```cargo` doc comment used by cargo-script and rust-script
+///
+/// frontmatter lines are blanked out so that diagnostics keep the original line numbers
+fn extract_manifest(source: &str) -> (Option<String>, String) {
+    let lines: Vec<&str> = source.lines().collect();
+
+    // the shebang and leading blank lines may come before the manifest
+    let start = lines
+        .iter()
+        .position(|l| !l.trim().is_empty() && !l.starts_with("#!"))
+        .unwrap_or(lines.len());
+
+    let Some(first) = lines.get(start) else {
+        return (None, source.to_string());
+    };
+
+    let opener = first.trim_end();
+    if opener == "---" || opener == "---cargo" {
+        if let Some(len) = lines[start + 1..]
+            .iter()
+            .position(|l| l.trim_end() == "---")
+        {
+            let end = start + 1 + len;
+            let manifest = lines[start + 1..end].join("\n");
+
+            let code = lines
+                .iter()
+                .enumerate()
+                .map(|(i, l)| if (start..=end).contains(&i) { "" } else { l })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            return (Some(manifest), code);
+        }
+    }
+
+    let mut manifest = None;
+    let mut inside = false;
+    let mut collected = Vec::new();
+
+    for line in &lines[start..] {
+        let Some(doc) = line.trim_start().strip_prefix("//!") else {
+            break;
+        };
+        let doc = doc.strip_prefix(' ').unwrap_or(doc);
+
+        if inside {
+            if doc.trim_end() == "```" {
+                manifest = Some(collected.join("\n"));
+                break;
+            }
+            collected.push(doc);
+        } else if doc.trim_end() == "```cargo" {
+            inside = true;
+        }
+    }
+
+    (manifest, source.to_string())
+}
+
+/// creates a valid `Cargo.toml` out of the embedded manifest and `--crate` flags
+fn build_manifest(name: &str, embedded: Option<&str>, crates: &[String]) -> anyhow::Result<String> {
+    let mut manifest: toml::Table = match embedded {
+        Some(m) => m
+            .parse()
+            .map_err(|e| anyhow!("invalid embedded manifest: {e}"))?,
+        None => toml::Table::new(),
+    };
+
+    let package = manifest
+        .entry("package")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or(anyhow!(
+            "invalid embedded manifest: `package` must be a table"
+        ))?;
+
+    package.insert("name".into(), name.into());
+    package.entry("version").or_insert_with(|| "0.0.0".into());
+    package
+        .entry("edition")
+        .or_insert_with(|| DEFAULT_EDITION.into());
+
+    let dependencies = manifest
+        .entry("dependencies")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or(anyhow!(
+            "invalid embedded manifest: `dependencies` must be a table"
+        ))?;
+
+    for spec in crates {
+        let (name, version) = parse_crate_spec(spec)?;
+        dependencies.insert(name, version.into());
+    }
+
+    // keeps the generated project out of any enclosing workspace
+    manifest
+        .entry("workspace")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+
+    Ok(toml::to_string(&manifest)?)
+}
+
+/// parses `name@version`, the version defaults to `*`
+pub fn parse_crate_spec(spec: &str) -> anyhow::Result<(String, String)> {
+    let (name, version) = spec.split_once('@').unwrap_or((spec, "*"));
+
+    if name.is_empty() || version.is_empty() {
+        return Err(anyhow!("invalid crate '{spec}', expected `name@version`"));
+    }
+
+    Ok((name.to_string(), version.to_string()))
+}
+
+fn package_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("main")
+        .to_lowercase();
+
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+
+    if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name
+    } else {
+        format!("script-{name}")
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    command::docker::DockerImage, core::BuildOutput, file_type::FileType, pin::Tool,
+    toolchain::Toolchain, utils::project_cache_dir,
+};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// where the directory of the nearest `tsconfig.json` is mounted when running inside docker
+const DOCKER_PROJECT_DIR: &str = "/root/project";
+
+/// The tool used to run typescript files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TsRuntime {
+    Bun,
+    Deno,
+    Tsx,
+    TsNode,
+    /// compiles with `tsc` and runs the output with `node`
+    Tsc,
+}
+
+impl TsRuntime {
+    /// in order of preference
+    pub const ALL: [Self; 5] = [Self::Bun, Self::Deno, Self::Tsx, Self::TsNode, Self::Tsc];
+
+    pub fn binary(&self) -> &'static str {
+        match self {
+            Self::Bun => "bun",
+            Self::Deno => "deno",
+            Self::Tsx => "tsx",
+            Self::TsNode => "ts-node",
+            Self::Tsc => "tsc",
+        }
+    }
+
+    /// the tool whose pinned version applies, `.nvmrc` for the node based runtimes
+    pub fn pin_tool(&self) -> Tool {
+        match self {
+            Self::Bun => Tool::Bun,
+            Self::Deno => Tool::Deno,
+            Self::Tsx | Self::TsNode | Self::Tsc => Tool::Node,
+        }
+    }
+
+    /// the first installed runtime
+    pub async fn detect() -> Option<Self> {
+        for runtime in Self::ALL {
+            if Toolchain::discover(runtime.binary()).await.is_some() {
+                log::debug!("typescript runs with {}", runtime.binary());
+                return Some(runtime);
+            }
+        }
+
+        log::debug!("no typescript runtime installed");
+        None
+    }
+
+    /// command running `path` directly, `None` for runtimes needing a build step
+    pub fn command(&self, path: &Path) -> Option<Command> {
+        let tsconfig = find_tsconfig(path);
+        let mut command = Command::new(self.binary());
+
+        match self {
+            Self::Bun => {
+                command.arg("run");
+            }
+            Self::Deno => {
+                command.args(["run", "--allow-all"]);
+            }
+            Self::Tsx => {
+                if let Some(tsconfig) = tsconfig {
+                    command.arg("--tsconfig").arg(tsconfig);
+                }
+            }
+            Self::TsNode => {
+                if let Some(tsconfig) = tsconfig {
+                    command.arg("--project").arg(tsconfig);
+                }
+            }
+            Self::Tsc => return None,
+        }
+
+        command.arg(path);
+        Some(command)
+    }
+
+    /// compiles `path` with `tsc` into the cache directory,
+    /// returning the build result and the command running the emitted javascript
+    pub async fn compile(
+        path: &Path,
+        env: &[(String, String)],
+    ) -> anyhow::Result<(BuildOutput, Command)> {
+        let out = project_cache_dir("typescript", path)?;
+
+        let mut tsc = Command::new(Self::Tsc.binary());
+        tsc.envs(env.iter().cloned())
+            .args(["--pretty", "false", "--noEmit", "false", "--outDir"])
+            .arg(&out);
+
+        // the root dir makes the location of the emitted file predictable
+        let (root, relative) = match find_tsconfig(path) {
+            Some(tsconfig) => {
+                let root = tsconfig.parent().unwrap_or(Path::new(".")).to_path_buf();
+                tsc.arg("--project")
+                    .arg(&tsconfig)
+                    .arg("--rootDir")
+                    .arg(&root);
+
+                let relative = std::fs::canonicalize(path)?
+                    .strip_prefix(std::fs::canonicalize(&root)?)?
+                    .to_path_buf();
+
+                (root, relative)
+            }
+            None => {
+                let root = std::fs::canonicalize(path)?
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default();
+                tsc.args(["--target", "es2022", "--module", "commonjs"])
+                    .arg("--rootDir")
+                    .arg(&root)
+                    .arg(path);
+
+                let relative = PathBuf::from(path.file_name().unwrap_or_default());
+
+                (root, relative)
+            }
+        };
+
+        let build = BuildOutput::capture(&FileType::Typescript, &mut tsc).await?;
+
+        let mut command = Command::new("node");
+        command
+            .current_dir(root)
+            .arg(out.join(relative.with_extension("js")));
+
+        Ok((build, command))
+    }
+
+    pub fn docker_image(&self) -> DockerImage {
+        match self {
+            Self::Bun => DockerImage::alpine("oven/bun"),
+            Self::Deno => DockerImage::alpine("denoland/deno"),
+            Self::Tsx | Self::TsNode | Self::Tsc => DockerImage::alpine("node"),
+        }
+    }
+
+    /// the same invocation as the local one, inside `docker_image`
+    ///
+    /// when there is a `tsconfig.json` its whole directory is mounted (see `docker_volumes`),
+    /// so the config and the imports of the watched file resolve as they do locally
+    pub fn docker_command(&self, path: &Path, entrypoint: &str) -> String {
+        let project = docker_project(path);
+
+        let (dir, file, tsconfig) = match &project {
+            Some((_, relative)) => (DOCKER_PROJECT_DIR, relative.as_str(), true),
+            None => ("/root/app", entrypoint, false),
+        };
+
+        let run = match self {
+            Self::Bun => format!("bun run {file}"),
+            Self::Deno => format!("deno run --allow-all {file}"),
+            Self::Tsx if tsconfig => format!("npx --yes tsx --tsconfig tsconfig.json {file}"),
+            Self::Tsx => format!("npx --yes tsx {file}"),
+            Self::TsNode if tsconfig => {
+                format!("npx --yes ts-node --project tsconfig.json {file}")
+            }
+            Self::TsNode => format!("npx --yes ts-node {file}"),
+            Self::Tsc => {
+                let options = if tsconfig {
+                    "--project tsconfig.json --rootDir .".to_string()
+                } else {
+                    format!("--target es2022 --module commonjs {file}")
+                };
+
+                let emitted = Path::new(file).with_extension("js");
+                format!(
+                    "npx --yes -p typescript tsc --pretty false --noEmit false --outDir /tmp/out {options} && node /tmp/out/{}",
+                    emitted.display()
+                )
+            }
+        };
+
+        format!("cd {dir} && {run}")
+    }
+
+    pub fn docker_volumes(path: &Path) -> Vec<String> {
+        docker_project(path)
+            .map(|(root, _)| vec![format!("{}:{DOCKER_PROJECT_DIR}", root.display())])
+            .unwrap_or_default()
+    }
+}
+
+/// the directory of the nearest `tsconfig.json` and the path of the file relative to it
+fn docker_project(path: &Path) -> Option<(PathBuf, String)> {
+    let root = find_tsconfig(path)?.parent()?.to_path_buf();
+    let relative = std::fs::canonicalize(path)
+        .ok()?
+        .strip_prefix(&root)
+        .ok()?
+        .display()
+        .to_string();
+
+    Some((root, relative))
+}
+
+/// finds the closest `tsconfig.json`, walking up from `path`
+pub fn find_tsconfig(path: &Path) -> Option<PathBuf> {
+    let path = std::fs::canonicalize(path).ok()?;
+
+    path.ancestors()
+        .skip(1)
+        .map(|dir| dir.join("tsconfig.json"))
+        .find(|tsconfig| tsconfig.is_file())
+}
@@ -0,0 +1,261 @@
+use crate::cli::RunArgs;
+use anyhow::anyhow;
+use colored::*;
+use notify::{
+    event::{DataChange, ModifyKind},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use runner::{
+    core::{prepare, RunOptions},
+    daemon::{self, Daemon, Readiness},
+    diagnostic,
+    file_type::FileType,
+    proxy::{self, Port, Proxy},
+    utils::clear_screen,
+};
+use std::{path::Path, time::Instant};
+use tokio::{select, sync::mpsc};
+
+/// keeps the file running as a server, replacing it with its new version on every change
+pub async fn run(args: &RunArgs) -> anyhow::Result<()> {
+    let path = args
+        .path
+        .as_deref()
+        .ok_or(anyhow!("--serve needs the file of the server"))?;
+
+    let file_type = FileType::try_from(&path.to_path_buf())?;
+    let mut options = args.run_options()?;
+    let readiness = args.readiness();
+
+    // the proxy holds the host port, so the versions publish theirs on a free one
+    let proxy = match args.port {
+        Some(port) => {
+            options.port = Some(Port {
+                host: None,
+                target: Some(port.container()),
+            });
+            Some((
+                Proxy::bind(port.host.unwrap_or_default(), args.live_reload).await?,
+                port,
+            ))
+        }
+        None => None,
+    };
+
+    let (tx, mut rx) = mpsc::channel(10);
+    let mut watcher = RecommendedWatcher::new(
+        move |result: std::result::Result<Event, notify::Error>| {
+            tx.blocking_send(result).expect("failed to send event");
+        },
+        notify::Config::default(),
+    )?;
+
+    watcher.watch(path, RecursiveMode::NonRecursive)?;
+
+    clear_screen();
+    eprintln!("🚀 Serving {}", path.display().to_string().yellow());
+    if let Some((_, port)) = &proxy {
+        let url = format!("http://localhost:{}", port.host.unwrap_or_default());
+        eprintln!("🔌 Proxying {}", url.cyan());
+    }
+    eprintln!();
+
+    let mut server = match restart(
+        &file_type,
+        path,
+        &options,
+        &readiness,
+        proxy.as_ref(),
+        None,
+        Instant::now(),
+    )
+    .await
+    {
+        Restarted::Serving(server) => server,
+        Restarted::Interrupted => return Ok(()),
+    };
+
+    loop {
+        select! {
+            res = rx.recv() => {
+                let event = res.ok_or(anyhow!("Failed to receive event"))??;
+                log::trace!("watcher event {:?} on {:?}", event.kind, event.paths);
+
+                if !matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(DataChange::Content))
+                ) {
+                    continue;
+                }
+
+                let changed = Instant::now();
+                eprintln!();
+                eprintln!("🏃File Changed!");
+
+                server = match restart(
+                    &file_type,
+                    path,
+                    &options,
+                    &readiness,
+                    proxy.as_ref(),
+                    server,
+                    changed,
+                )
+                .await
+                {
+                    Restarted::Serving(server) => server,
+                    Restarted::Interrupted => break,
+                };
+            }
+
+            status = daemon::exited(&mut server) => {
+                let status = status.map_or("an error".to_string(), |s| s.to_string());
+                eprintln!();
+                eprintln!("💥 The server exited with {}, it starts again on the next change", status.red());
+                server = None;
+                if let Some((proxy, _)) = &proxy {
+                    proxy.set_upstream(None).await;
+                }
+            }
+
+            _ = tokio::signal::ctrl_c() => {
+                eprintln!("🧼 Cleaning up...");
+                if let Some(server) = server {
+                    server.stop().await;
+                }
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// What serves once `restart` returns
+// only ever returned, never stored, its size doesn't matter
+#[allow(clippy::large_enum_variant)]
+enum Restarted {
+    Serving(Option<Daemon>),
+    /// Ctrl-C was pressed while the new version got ready, both versions are stopped
+    Interrupted,
+}
+
+/// starts the new version of the server and stops `old` once it's ready,
+/// `old` keeps serving when the new version fails to build or to become ready
+async fn restart(
+    file_type: &FileType,
+    path: &Path,
+    options: &RunOptions,
+    readiness: &Readiness,
+    proxy: Option<&(Proxy, Port)>,
+    mut old: Option<Daemon>,
+    changed: Instant,
+) -> Restarted {
+    let restarting = old.is_some();
+
+    // a local server without a target port takes the free one it's given, or any other
+    let mut options = options.clone();
+    if proxy.is_some_and(|(_, port)| port.target.is_none()) {
+        match proxy::free_port() {
+            Ok(port) => options.env.push(format!("PORT={port}")),
+            Err(e) => log::debug!("no free port for $PORT: {e}"),
+        }
+    }
+
+    let prepared = match prepare(file_type, path, &options).await {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            eprintln!("💥 {e}");
+            return Restarted::Serving(old);
+        }
+    };
+
+    diagnostic::render(prepared.diagnostics());
+    if prepared.build_failed() {
+        eprintln!("💥 Build failed, {}", keeps_serving(&old));
+        return Restarted::Serving(old);
+    }
+
+    // a port of localhost can't be shared, the old version has to let go of it first,
+    // containers have ports of their own
+    let local = prepared.command().is_some();
+    if local && (readiness.port.is_some() || proxy.is_some_and(|(_, p)| p.target.is_some())) {
+        if let Some(old) = old.take() {
+            old.stop().await;
+        }
+    }
+
+    let started = Instant::now();
+    let mut server = match prepared.spawn(readiness.pattern.clone()).await {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("💥 {e}, {}", keeps_serving(&old));
+            return Restarted::Serving(old);
+        }
+    };
+
+    // behind the proxy the ready port is the proxy's, the server is probed where it listens
+    let ready = async {
+        let upstream = match proxy {
+            Some((_, port)) => Some(server.address(port, readiness.timeout).await?),
+            None => None,
+        };
+        server.wait_ready(readiness, upstream).await?;
+        anyhow::Ok(upstream)
+    };
+
+    // getting ready can take up to `--ready-timeout`, Ctrl-C must not wait for it
+    let ready = select! {
+        ready = ready => Some(ready),
+        _ = tokio::signal::ctrl_c() => None,
+    };
+
+    let upstream = match ready {
+        Some(Ok(upstream)) => upstream,
+        Some(Err(e)) => {
+            eprintln!("💥 {e}, {}", keeps_serving(&old));
+            server.stop().await;
+            return Restarted::Serving(old);
+        }
+        None => {
+            eprintln!("🧼 Cleaning up...");
+            server.stop().await;
+            if let Some(old) = old {
+                old.stop().await;
+            }
+            return Restarted::Interrupted;
+        }
+    };
+
+    if let (Some((proxy, _)), Some(address)) = (proxy, upstream) {
+        eprintln!("🔌 Listening on {}", address.to_string().cyan());
+        proxy.set_upstream(Some(address)).await;
+        proxy.reload();
+    }
+
+    let latency = format!("{:?}", changed.elapsed()).dimmed();
+    let (build, ready) = (prepared.build_duration(), started.elapsed());
+
+    if let Some(old) = old {
+        old.stop().await;
+    }
+
+    if restarting {
+        eprintln!("🔁 Restarted in {latency} [{build:?} build, {ready:?} until ready]");
+    } else {
+        eprintln!("✅ Ready in {latency} [{build:?} build, {ready:?} until ready]");
+    }
+
+    if let Some(image) = &server.image {
+        eprintln!("📦 Image: {image}");
+    }
+
+    Restarted::Serving(Some(server))
+}
+
+fn keeps_serving(old: &Option<Daemon>) -> &'static str {
+    match old {
+        Some(_) => "the previous version keeps serving",
+        None => "nothing is serving until the next change",
+    }
+}
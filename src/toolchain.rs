@@ -0,0 +1,172 @@
+use std::{
+    cmp::Ordering,
+    env, fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tokio::process::Command;
+
+/// A `major.minor.patch` version, missing parts are zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// finds the first version in the output of `--version`,
+    /// e.g. `3.11.2` in `Python 3.11.2` or `1.22.1` in `go version go1.22.1 linux/amd64`
+    ///
+    /// dotted versions are preferred, so `perl 5, version 36 (v5.36.0)` gives `5.36.0`
+    pub fn parse_output(output: &str) -> Option<Self> {
+        let candidates: Vec<&str> = output
+            .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .map(|s| s.trim_matches('.'))
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        candidates
+            .iter()
+            .find(|s| s.contains('.'))
+            .or(candidates.first())
+            .and_then(|s| s.parse().ok())
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s
+            .trim()
+            .trim_start_matches('v')
+            .split('.')
+            .map(|p| p.parse::<u32>());
+
+        let mut next = || parts.next().transpose().map(|p| p.unwrap_or(0));
+
+        Ok(Self::new(next()?, next()?, next()?))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// An installed tool, resolved on the `PATH`
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub binary: String,
+    pub path: PathBuf,
+    /// `None` when the version could not be read from the tool's output
+    pub version: Option<Version>,
+}
+
+impl Toolchain {
+    /// resolves `binary` and reads its version,
+    /// `None` when it's not installed or its version command fails
+    pub async fn discover(binary: &str) -> Option<Self> {
+        let path = which(binary)?;
+
+        let output = Command::new(&path)
+            .args(version_args(binary))
+            .output()
+            .await
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        // some tools (e.g. java) print their version on stderr
+        let text = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let version = Version::parse_output(&text);
+        log::trace!("{binary} is {} at version {version:?}", path.display());
+
+        Some(Self {
+            binary: binary.to_string(),
+            path,
+            version,
+        })
+    }
+
+    /// like `discover`, rejecting versions lower than `min`
+    /// an unreadable version is accepted
+    pub async fn discover_min(binary: &str, min: Option<Version>) -> Option<Self> {
+        let toolchain = Self::discover(binary).await?;
+
+        match (min, toolchain.version) {
+            (Some(min), Some(version)) if version.cmp(&min) == Ordering::Less => None,
+            _ => Some(toolchain),
+        }
+    }
+}
+
+/// the absolute path of `binary`, looked up on the `PATH` like a shell would
+pub fn which(binary: &str) -> Option<PathBuf> {
+    let candidate = Path::new(binary);
+    if candidate.components().count() > 1 {
+        return is_executable(candidate).then(|| candidate.to_path_buf());
+    }
+
+    which_in(binary, env::split_paths(&env::var_os("PATH")?))
+}
+
+/// the first executable named `binary` in `dirs`
+pub fn which_in(binary: &str, dirs: impl IntoIterator<Item = PathBuf>) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) {
+        &["exe", "cmd", "bat"]
+    } else {
+        &[]
+    };
+
+    dirs.into_iter().find_map(|dir| {
+        let path = dir.join(binary);
+        if is_executable(&path) {
+            return Some(path);
+        }
+
+        extensions
+            .iter()
+            .map(|e| path.with_extension(e))
+            .find(|p| is_executable(p))
+    })
+}
+
+fn version_args(binary: &str) -> &'static [&'static str] {
+    match binary {
+        // `-version` is the only flag understood by every JDK
+        "java" | "javac" | "scalac" => &["-version"],
+        "go" => &["version"],
+        _ => &["--version"],
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,386 @@
+use crate::cli::UpArgs;
+use anyhow::{anyhow, bail, Context};
+use clap::ValueEnum;
+use colored::*;
+use notify::{
+    event::{DataChange, ModifyKind},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use runner::{
+    core::{prepare, RunOptions, RunStatus},
+    daemon::{self, Daemon},
+    diagnostic,
+    file_type::FileType,
+    utils::clear_screen,
+};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use tokio::{
+    select,
+    sync::{mpsc, watch},
+};
+
+/// the colours of the process names, in order
+const COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+    Color::Red,
+];
+
+/// A program of `runner up`, watched and restarted on its own
+#[derive(Debug, Clone)]
+struct Process {
+    name: String,
+    path: PathBuf,
+    /// detected from the extension when not set
+    runtime: Option<FileType>,
+    /// `KEY=VALUE` variables passed to the program
+    env: Vec<String>,
+    args: Vec<String>,
+    no_docker: bool,
+}
+
+/// The `runner.toml` of a project, only its processes matter here
+#[derive(Debug, Deserialize)]
+struct Config {
+    #[serde(default)]
+    processes: BTreeMap<String, Entry>,
+}
+
+/// `name = "file"`, or a table for everything else
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    File(PathBuf),
+    Process {
+        file: PathBuf,
+        runtime: Option<String>,
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default = "docker_default")]
+        docker: bool,
+    },
+}
+
+fn docker_default() -> bool {
+    true
+}
+
+/// runs every process of the Procfile or `runner.toml`, restarting each one when its file changes
+pub async fn run(args: &UpArgs) -> anyhow::Result<()> {
+    let processes = load(args.config.as_deref())?;
+    let width = processes
+        .iter()
+        .map(|(p, _)| p.name.len())
+        .max()
+        .unwrap_or_default();
+
+    let (tx, mut rx) = mpsc::channel(10);
+    let mut watcher = RecommendedWatcher::new(
+        move |result: std::result::Result<Event, notify::Error>| {
+            tx.blocking_send(result).expect("failed to send event");
+        },
+        notify::Config::default(),
+    )?;
+
+    for (process, _) in &processes {
+        watcher.watch(&process.path, RecursiveMode::NonRecursive)?;
+    }
+
+    clear_screen();
+    let names: Vec<&str> = processes.iter().map(|(p, _)| p.name.as_str()).collect();
+    eprintln!("🚀 Starting {}", names.join(", ").yellow());
+    eprintln!();
+
+    let stop = watch::Sender::new(false);
+    let mut supervised = vec![];
+    for (i, (process, file_type)) in processes.into_iter().enumerate() {
+        let prefix = format!("{:<width$} │ ", process.name)
+            .color(COLORS[i % COLORS.len()])
+            .to_string();
+
+        let options = RunOptions {
+            no_docker: args.no_docker || process.no_docker,
+            env: process.env.clone(),
+            args: process.args.clone(),
+            prefix: Some(prefix.clone()),
+            ..Default::default()
+        };
+
+        let (restart, restarts) = watch::channel(());
+        let path = process.path.clone();
+        let task = tokio::spawn(supervise(
+            process,
+            file_type,
+            options,
+            prefix,
+            restarts,
+            stop.subscribe(),
+        ));
+        supervised.push((path, restart, task));
+    }
+
+    loop {
+        select! {
+            res = rx.recv() => {
+                let event = res.ok_or(anyhow!("Failed to receive event"))??;
+                log::trace!("watcher event {:?} on {:?}", event.kind, event.paths);
+
+                if !matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(DataChange::Content))
+                ) {
+                    continue;
+                }
+
+                for (path, restart, _) in &supervised {
+                    if event.paths.iter().any(|changed| changed == path) {
+                        restart.send_replace(());
+                    }
+                }
+            }
+
+            _ = tokio::signal::ctrl_c() => {
+                eprintln!();
+                eprintln!("🧼 Cleaning up...");
+                // every process is asked to exit, and killed after a grace period
+                stop.send_replace(true);
+                for (_, _, task) in supervised {
+                    let _ = task.await;
+                }
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// runs `process` over and over, every time `restart` changes, until `stop` turns true
+async fn supervise(
+    process: Process,
+    file_type: FileType,
+    options: RunOptions,
+    prefix: String,
+    mut restart: watch::Receiver<()>,
+    mut stop: watch::Receiver<bool>,
+) {
+    loop {
+        // a single save can report several changes, they are all seen by this start
+        restart.borrow_and_update();
+        eprintln!("{prefix}🏃 Starting {}", process.path.display());
+
+        let mut server = select! {
+            server = start(&file_type, &process.path, &options, &prefix) => server,
+            _ = restart.changed() => continue,
+            _ = stopping(&mut stop) => return,
+        };
+        let started = Instant::now();
+
+        loop {
+            select! {
+                status = daemon::exited(&mut server) => {
+                    server = None;
+                    let status = match status.map(RunStatus::from) {
+                        Ok(RunStatus::Success) => "exited".green(),
+                        Ok(status) => format!("exited with {status}").red(),
+                        Err(e) => format!("failed: {e}").red(),
+                    };
+                    eprintln!(
+                        "{prefix}🏁 {status} after {:.2?}, it starts again on the next change",
+                        started.elapsed()
+                    );
+                }
+
+                _ = restart.changed() => break,
+
+                _ = stopping(&mut stop) => {
+                    if let Some(server) = server {
+                        server.stop().await;
+                    }
+                    return;
+                }
+            }
+        }
+
+        if let Some(server) = server {
+            eprintln!("{prefix}🔁 File changed, restarting");
+            server.stop().await;
+        }
+    }
+}
+
+/// resolves once `stop` turns true, or when nothing can stop the process anymore
+async fn stopping(stop: &mut watch::Receiver<bool>) {
+    let _ = stop.wait_for(|stop| *stop).await;
+}
+
+/// builds `path` and starts it in the background, `None` when it can't be started
+async fn start(
+    file_type: &FileType,
+    path: &Path,
+    options: &RunOptions,
+    prefix: &str,
+) -> Option<Daemon> {
+    let prepared = match prepare(file_type, path, options).await {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            eprintln!("{prefix}💥 {e:#}, it starts again on the next change");
+            return None;
+        }
+    };
+
+    if prepared.build_failed() {
+        eprintln!("{prefix}💥 Build failed, it starts again on the next change");
+        diagnostic::render(prepared.diagnostics());
+        return None;
+    }
+
+    match prepared.spawn(None).await {
+        Ok(server) => Some(server),
+        Err(e) => {
+            eprintln!("{prefix}💥 {e:#}, it starts again on the next change");
+            None
+        }
+    }
+}
+
+/// the processes of `config`, or of the `runner.toml` or Procfile of the current directory,
+/// with their runtimes
+fn load(config: Option<&Path>) -> anyhow::Result<Vec<(Process, FileType)>> {
+    let path = match config {
+        Some(path) => path.to_path_buf(),
+        None => ["runner.toml", "Procfile"]
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| {
+                path.is_file()
+                    && (path.ends_with("Procfile")
+                        || std::fs::read_to_string(path)
+                            .is_ok_and(|content| content.contains("[processes")))
+            })
+            .ok_or(anyhow!(
+                "no Procfile or runner.toml with a [processes] table in the current directory"
+            ))?,
+    };
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("unable to read '{}'", path.display()))?;
+
+    let processes = match path.extension() {
+        Some(ext) if ext == "toml" => parse_toml(&content),
+        _ => parse_procfile(&content),
+    }
+    .with_context(|| format!("invalid processes in '{}'", path.display()))?;
+
+    if processes.is_empty() {
+        bail!("'{}' has no processes", path.display());
+    }
+
+    // the files are relative to the config, the watcher reports absolute paths
+    let dir = path.parent().unwrap_or(Path::new(""));
+    processes
+        .into_iter()
+        .map(|mut process| {
+            let file = dir.join(&process.path);
+            process.path = file.canonicalize().with_context(|| {
+                format!("{}: unable to find '{}'", process.name, file.display())
+            })?;
+
+            let file_type = match &process.runtime {
+                Some(runtime) => runtime.clone(),
+                None => FileType::try_from(&process.path)?,
+            };
+            if matches!(file_type, FileType::Unsupported) {
+                bail!(
+                    "{}: unsupported file type: '{}'",
+                    process.name,
+                    file.display()
+                );
+            }
+
+            Ok((process, file_type))
+        })
+        .collect()
+}
+
+/// `name: [KEY=VALUE...] FILE [ARGS...]` lines, `#` starts a comment
+fn parse_procfile(content: &str) -> anyhow::Result<Vec<Process>> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, command) = line
+                .split_once(':')
+                .ok_or(anyhow!("expected `name: file`, got '{line}'"))?;
+
+            let mut words = command.split_whitespace().peekable();
+            let mut env = vec![];
+            while let Some(assignment) = words.next_if(|w| w.contains('=')) {
+                env.push(assignment.to_string());
+            }
+
+            let file = words
+                .next()
+                .ok_or(anyhow!("{}: the file to run is missing", name.trim()))?;
+
+            Ok(Process {
+                name: name.trim().to_string(),
+                path: PathBuf::from(file),
+                runtime: None,
+                env,
+                args: words.map(String::from).collect(),
+                no_docker: false,
+            })
+        })
+        .collect()
+}
+
+/// the `[processes]` table of a `runner.toml`
+fn parse_toml(content: &str) -> anyhow::Result<Vec<Process>> {
+    let config: Config = toml::from_str(content)?;
+
+    config
+        .processes
+        .into_iter()
+        .map(|(name, entry)| match entry {
+            Entry::File(path) => Ok(Process {
+                name,
+                path,
+                runtime: None,
+                env: vec![],
+                args: vec![],
+                no_docker: false,
+            }),
+            Entry::Process {
+                file,
+                runtime,
+                env,
+                args,
+                docker,
+            } => Ok(Process {
+                runtime: runtime
+                    .map(|runtime| {
+                        FileType::from_str(&runtime, true)
+                            .map_err(|_| anyhow!("{name}: unknown runtime '{runtime}'"))
+                    })
+                    .transpose()?,
+                name,
+                path: file,
+                env: env.into_iter().map(|(k, v)| format!("{k}={v}")).collect(),
+                args,
+                no_docker: !docker,
+            }),
+        })
+        .collect()
+}
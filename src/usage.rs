@@ -0,0 +1,326 @@
+use crate::utils::format_bytes;
+use serde::Serialize;
+use std::{
+    fmt, io,
+    process::{ExitStatus, Output},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    process::{Child, Command},
+};
+
+#[cfg(unix)]
+use tokio::process::{ChildStderr, ChildStdout};
+
+/// Resources used by a run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Usage {
+    /// reported by `wait4` for a local process and the children it waited for
+    Process {
+        user: Duration,
+        system: Duration,
+        /// peak resident set size, in bytes
+        max_rss: u64,
+        minor_faults: u64,
+        major_faults: u64,
+        voluntary_switches: u64,
+        involuntary_switches: u64,
+    },
+    /// sampled with `docker stats` while the container runs,
+    /// short lived containers may exit before the first sample
+    Container {
+        /// highest memory usage seen, in bytes
+        max_memory: u64,
+        /// mean of the sampled cpu percentages
+        cpu_percent: f64,
+    },
+}
+
+impl Usage {
+    /// peak memory in bytes, to compare runs
+    pub fn peak_memory(&self) -> u64 {
+        match self {
+            Self::Process { max_rss, .. } => *max_rss,
+            Self::Container { max_memory, .. } => *max_memory,
+        }
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Process {
+                user,
+                system,
+                max_rss,
+                minor_faults,
+                major_faults,
+                voluntary_switches,
+                involuntary_switches,
+            } => write!(
+                f,
+                "{user:.2?} user, {system:.2?} sys, {} peak, {} faults ({major_faults} major), {} context switches",
+                format_bytes(*max_rss),
+                minor_faults + major_faults,
+                voluntary_switches + involuntary_switches
+            ),
+            Self::Container {
+                max_memory,
+                cpu_percent,
+            } => write!(
+                f,
+                "{cpu_percent:.1}% cpu, {} peak",
+                format_bytes(*max_memory)
+            ),
+        }
+    }
+}
+
+/// spawns `command` and waits for it like `wait_with_output`, also collecting its resource usage with `wait4`
+///
+/// the output is empty for the streams that were not piped, `tee` echoes the piped ones to ours,
+/// every line after the prefix it holds
+#[cfg(unix)]
+pub async fn wait(command: &mut Command, tee: Option<&str>) -> io::Result<(Output, Option<Usage>)> {
+    // spawned with std so that only `wait4` reaps it, tokio would wait on the pid as well
+    let mut child = command.as_std_mut().spawn()?;
+    let pid = child.id();
+
+    // dropping this future, when a run is cancelled, takes the program down with it
+    let mut running = Running(Some(pid));
+
+    let stdout = child.stdout.take().map(ChildStdout::from_std).transpose()?;
+    let stderr = child.stderr.take().map(ChildStderr::from_std).transpose()?;
+
+    // the program is left unreaped, its pid can't be reused until `wait4` below
+    let exited = async {
+        tokio::task::spawn_blocking(move || wait_exited(pid))
+            .await
+            .map_err(io::Error::other)?
+    };
+
+    let (stdout, stderr, ()) = tokio::try_join!(
+        read_all(stdout, tee.map(|prefix| (tokio::io::stdout(), prefix))),
+        read_all(stderr, tee.map(|prefix| (tokio::io::stderr(), prefix))),
+        exited
+    )?;
+
+    // it has exited, so this doesn't block, and nothing can cancel it in between
+    let (status, usage) = wait4(pid)?;
+    running.0 = None;
+
+    Ok((
+        Output {
+            status,
+            stdout,
+            stderr,
+        },
+        Some(usage),
+    ))
+}
+
+/// Kills the process when dropped before it's reaped, and reaps it
+#[cfg(unix)]
+struct Running(Option<u32>);
+
+#[cfg(unix)]
+impl Drop for Running {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            log::debug!("killing process {pid}, its run was cancelled");
+            // SAFETY: only `wait4` reaps the process and it hasn't, so the pid is still its own
+            unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+            std::thread::spawn(move || wait4(pid));
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn wait(command: &mut Command, tee: Option<&str>) -> io::Result<(Output, Option<Usage>)> {
+    Ok((wait_with_output(command.spawn()?, tee).await?, None))
+}
+
+/// `Child::wait_with_output`, `tee` echoes the piped streams to ours as they're read,
+/// every line after the prefix it holds
+pub async fn wait_with_output(mut child: Child, tee: Option<&str>) -> io::Result<Output> {
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    let (stdout, stderr, status) = tokio::try_join!(
+        read_all(stdout, tee.map(|prefix| (tokio::io::stdout(), prefix))),
+        read_all(stderr, tee.map(|prefix| (tokio::io::stderr(), prefix))),
+        child.wait()
+    )?;
+
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+async fn read_all(
+    pipe: Option<impl AsyncRead + Unpin>,
+    mut echo: Option<(impl AsyncWrite + Unpin, &str)>,
+) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![];
+    let Some(mut pipe) = pipe else {
+        return Ok(buffer);
+    };
+
+    let mut chunk = [0; 8192];
+    loop {
+        let read = pipe.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(buffer);
+        }
+
+        if let Some((echo, prefix)) = &mut echo {
+            if prefix.is_empty() {
+                echo.write_all(&chunk[..read]).await?;
+            } else {
+                // the prefix goes at the start of every line, a line can span chunks
+                let at_line_start = buffer.last().is_none_or(|b| *b == b'\n');
+                for (i, line) in chunk[..read].split_inclusive(|b| *b == b'\n').enumerate() {
+                    if i > 0 || at_line_start {
+                        echo.write_all(prefix.as_bytes()).await?;
+                    }
+                    echo.write_all(line).await?;
+                }
+            }
+            echo.flush().await?;
+        }
+
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+}
+
+/// blocks until the process exits, without reaping it
+#[cfg(unix)]
+fn wait_exited(pid: u32) -> io::Result<()> {
+    // SAFETY: siginfo_t is plain old data, all zeroes is a valid value
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+
+    retry(|| {
+        // SAFETY: the pointer is valid for the duration of the call
+        unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        }
+    })
+}
+
+#[cfg(unix)]
+fn wait4(pid: u32) -> io::Result<(ExitStatus, Usage)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: rusage is plain old data, all zeroes is a valid value
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    retry(|| {
+        // SAFETY: both pointers are valid for the duration of the call
+        unsafe { libc::wait4(pid as libc::pid_t, &mut status, 0, &mut rusage) }
+    })?;
+
+    let time = |t: libc::timeval| {
+        Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+    };
+
+    // kilobytes everywhere but on macOS
+    let max_rss = if cfg!(target_os = "macos") {
+        rusage.ru_maxrss as u64
+    } else {
+        rusage.ru_maxrss as u64 * 1024
+    };
+
+    Ok((
+        ExitStatus::from_raw(status),
+        Usage::Process {
+            user: time(rusage.ru_utime),
+            system: time(rusage.ru_stime),
+            max_rss,
+            minor_faults: rusage.ru_minflt as u64,
+            major_faults: rusage.ru_majflt as u64,
+            voluntary_switches: rusage.ru_nvcsw as u64,
+            involuntary_switches: rusage.ru_nivcsw as u64,
+        },
+    ))
+}
+
+/// calls `call` until it isn't interrupted by a signal
+#[cfg(unix)]
+fn retry(mut call: impl FnMut() -> libc::c_int) -> io::Result<()> {
+    loop {
+        if call() != -1 {
+            return Ok(());
+        }
+
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Running totals of the `docker stats` samples of a container
+#[derive(Debug, Clone, Default)]
+pub struct ContainerSamples {
+    max_memory: u64,
+    cpu_total: f64,
+    count: usize,
+}
+
+impl ContainerSamples {
+    /// records a `{{.MemUsage}}\t{{.CPUPerc}}` line, e.g. `12.5MiB / 7.7GiB\t3.42%`
+    pub fn record(&mut self, line: &str) {
+        let Some((memory, cpu)) = line.trim().split_once('\t') else {
+            return;
+        };
+
+        let used = memory.split('/').next().unwrap_or_default();
+        let (Some(used), Ok(cpu)) = (
+            parse_bytes(used),
+            cpu.trim().trim_end_matches('%').parse::<f64>(),
+        ) else {
+            return;
+        };
+
+        self.max_memory = self.max_memory.max(used);
+        self.cpu_total += cpu;
+        self.count += 1;
+    }
+
+    pub fn usage(&self) -> Option<Usage> {
+        (self.count > 0).then(|| Usage::Container {
+            max_memory: self.max_memory,
+            cpu_percent: self.cpu_total / self.count as f64,
+        })
+    }
+}
+
+/// parses docker's sizes, e.g. `12.5MiB` or `1.2GB`
+fn parse_bytes(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = size.split_at(split);
+
+    let multiplier: f64 = match unit {
+        "B" => 1.0,
+        "kB" | "KB" => 1e3,
+        "KiB" => 1024.0,
+        "MB" => 1e6,
+        "MiB" => 1024.0 * 1024.0,
+        "GB" => 1e9,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+
+    Some((number.parse::<f64>().ok()? * multiplier) as u64)
+}
@@ -1,5 +1,11 @@
-use crate::file_type::FileType;
-use std::{env, path::PathBuf, process};
+use crate::{explain, file_type::FileType};
+use std::{
+    collections::hash_map::DefaultHasher,
+    env,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process,
+};
 use tokio::io::AsyncWriteExt;
 
 pub fn clear_screen() {
@@ -10,10 +16,99 @@ pub async fn temp_file(ft: &FileType) -> anyhow::Result<PathBuf> {
     let pid = process::id();
 
     let mut path = env::temp_dir();
-    path.push(&format!("runner-{pid}.{}", ft.get_extension()));
+    path.push(format!("runner-{pid}.{}", ft.get_extension()));
 
     let mut file = tokio::fs::File::create(&path).await?;
     file.write_all(ft.get_template().as_bytes()).await?;
 
     Ok(path)
 }
+
+/// directory where runner keeps generated projects and build caches
+/// defaults to `$XDG_CACHE_HOME/runner` or `~/.cache/runner`
+pub fn cache_dir() -> PathBuf {
+    let base = env::var("XDG_CACHE_HOME").unwrap_or_else(|_| "~/.cache".to_string());
+
+    PathBuf::from(shellexpand::tilde(&base).to_string()).join("runner")
+}
+
+/// cache directory dedicated to `path`, e.g. `~/.cache/runner/rust/hello-<hash>`
+/// the hash of the canonical path keeps files with the same name apart,
+/// it's only created when not explaining a run
+pub fn project_cache_dir(kind: &str, path: &Path) -> anyhow::Result<PathBuf> {
+    let canonical = std::fs::canonicalize(path)?;
+    let mut hasher = DefaultHasher::new();
+    canonical.hash(&mut hasher);
+
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("main");
+
+    let dir = cache_dir()
+        .join(kind)
+        .join(format!("{stem}-{:016x}", hasher.finish()));
+
+    if !explain::is_dry_run() {
+        std::fs::create_dir_all(&dir)?;
+    }
+    Ok(dir)
+}
+
+/// avoids touching files that did not change, so build tools can reuse their caches,
+/// creates the missing directories and writes nothing when explaining a run
+pub async fn write_if_changed(path: &Path, content: &str) -> anyhow::Result<()> {
+    if explain::is_dry_run() {
+        return Ok(());
+    }
+
+    if let Ok(current) = tokio::fs::read_to_string(path).await {
+        if current == content {
+            return Ok(());
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, content).await?;
+    Ok(())
+}
+
+/// pads the cells of `rows` into aligned columns, one line per row
+pub fn format_table(rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths = vec![];
+    for row in rows {
+        widths.resize(widths.len().max(row.len()), 0);
+
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
+/// `1536` as `1.5 KiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}